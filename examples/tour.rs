@@ -1,25 +1,33 @@
+// The application builds anywhere, but only opens a window on macOS.
+#![cfg_attr(not(target_os = "macos"), allow(dead_code, unused_imports))]
+
+#[cfg(target_os = "macos")]
 use cocoa::appkit::{
     NSApp, NSApplication, NSApplicationActivationPolicyRegular, NSBackingStoreBuffered, NSWindow,
     NSWindowStyleMask,
 };
+#[cfg(target_os = "macos")]
 use cocoa::base::{id, nil, NO};
+#[cfg(target_os = "macos")]
 use cocoa::foundation::{NSAutoreleasePool, NSPoint, NSRect, NSSize};
 
+#[cfg(target_os = "macos")]
+use iced_nsview::IcedView;
 use iced_nsview::{
     button, scrollable, slider, text_input, Application, Button, Checkbox, Color, Column, Command,
-    Container, Element, HorizontalAlignment, IcedView, Image, Length, Radio, Row, Scrollable,
-    Settings, Size, Slider, Space, Text, TextInput, Viewport,
+    Container, Element, HorizontalAlignment, Image, Length, MainThread, Radio, Row, Scrollable,
+    Settings, Size, Slider, Space, Text, TextInput,
 };
 
+#[cfg(target_os = "macos")]
 pub fn main() {
-    let size = Size::new(800, 600);
+    let size = Size::new(800.0, 600.0);
+    let main_thread = MainThread::new().expect("Not on the main thread");
     let app = unsafe { init_app() };
     let window = unsafe { init_window(&size) };
-    let scale_factor = unsafe { window.backingScaleFactor() };
 
     let tour = Tour::new();
-    let viewport = Viewport::with_physical_size(size, scale_factor);
-    let view = IcedView::new(tour, viewport, Settings::default());
+    let view = IcedView::new(main_thread, tour, size, Settings::default());
 
     unsafe {
         NSWindow::setContentView_(window, view.raw_object());
@@ -27,6 +35,12 @@ pub fn main() {
     }
 }
 
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("This example only runs on macOS.");
+}
+
+#[cfg(target_os = "macos")]
 unsafe fn init_app() -> id {
     let _pool = NSAutoreleasePool::new(nil);
     let app = NSApp();
@@ -35,7 +49,8 @@ unsafe fn init_app() -> id {
     app
 }
 
-unsafe fn init_window(size: &Size<u32>) -> id {
+#[cfg(target_os = "macos")]
+unsafe fn init_window(size: &Size) -> id {
     let window = NSWindow::alloc(nil)
         .initWithContentRect_styleMask_backing_defer_(
             NSRect::new(
@@ -0,0 +1,558 @@
+//! Animate values of the application from frame to frame.
+//!
+//! An [`Animated`] value lives in the state of the application. Setting its target starts a
+//! [`Transition`] from its current value, either a tween with an [`Easing`] curve or a
+//! [`Spring`]. The value advances with the frames of the display:
+//!
+//! - `Application::is_animating` returns whether any value is animating,
+//! - `Application::on_frame` turns the frame into a message,
+//! - `Application::update` calls [`Animated::tick`] with it.
+//!
+//! Values only advance by the time they're given, so animations are deterministic under a fake
+//! clock.
+//!
+//! [`Animated`]: struct.Animated.html
+//! [`Animated::tick`]: struct.Animated.html#method.tick
+//! [`Transition`]: enum.Transition.html
+//! [`Easing`]: enum.Easing.html
+//! [`Spring`]: struct.Spring.html
+
+use std::time::Duration;
+
+use crate::clock::Frame;
+use crate::{Color, Point, Size, Vector};
+
+/// A value which can be blended with another one.
+pub trait Interpolate: Copy {
+    /// Returns the value at `t` between `self`, at 0, and `other`, at 1.
+    ///
+    /// `t` may leave this range, e.g. when a spring overshoots.
+    fn interpolate(self, other: Self, t: f32) -> Self;
+
+    /// Returns the length of the way from `self` to `other`, projected on the way from `from`
+    /// to `to`, as a fraction of the latter.
+    ///
+    /// A spring uses it to keep its velocity when its target changes. By default, it's `None`,
+    /// and the spring starts again at rest.
+    fn ratio(self, _other: Self, _from: Self, _to: Self) -> Option<f32> {
+        None
+    }
+}
+
+impl Interpolate for f32 {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+
+    fn ratio(self, other: Self, from: Self, to: Self) -> Option<f32> {
+        project(&[other - self], &[to - from])
+    }
+}
+
+impl Interpolate for f64 {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        self + (other - self) * f64::from(t)
+    }
+
+    fn ratio(self, other: Self, from: Self, to: Self) -> Option<f32> {
+        let ratio = (other - self) / (to - from);
+
+        Some(ratio as f32).filter(|ratio| ratio.is_finite())
+    }
+}
+
+impl Interpolate for Point {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        Point::new(
+            self.x.interpolate(other.x, t),
+            self.y.interpolate(other.y, t),
+        )
+    }
+
+    fn ratio(self, other: Self, from: Self, to: Self) -> Option<f32> {
+        project(
+            &[other.x - self.x, other.y - self.y],
+            &[to.x - from.x, to.y - from.y],
+        )
+    }
+}
+
+impl Interpolate for Vector {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        Vector::new(
+            self.x.interpolate(other.x, t),
+            self.y.interpolate(other.y, t),
+        )
+    }
+
+    fn ratio(self, other: Self, from: Self, to: Self) -> Option<f32> {
+        project(
+            &[other.x - self.x, other.y - self.y],
+            &[to.x - from.x, to.y - from.y],
+        )
+    }
+}
+
+impl Interpolate for Size {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        Size::new(
+            self.width.interpolate(other.width, t),
+            self.height.interpolate(other.height, t),
+        )
+    }
+
+    fn ratio(self, other: Self, from: Self, to: Self) -> Option<f32> {
+        project(
+            &[other.width - self.width, other.height - self.height],
+            &[to.width - from.width, to.height - from.height],
+        )
+    }
+}
+
+impl Interpolate for Color {
+    /// Blends the components, which are clamped so overshooting stays a valid color.
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        let component = |from: f32, to: f32| from.interpolate(to, t).max(0.0).min(1.0);
+
+        Color {
+            r: component(self.r, other.r),
+            g: component(self.g, other.g),
+            b: component(self.b, other.b),
+            a: component(self.a, other.a),
+        }
+    }
+
+    fn ratio(self, other: Self, from: Self, to: Self) -> Option<f32> {
+        project(
+            &[
+                other.r - self.r,
+                other.g - self.g,
+                other.b - self.b,
+                other.a - self.a,
+            ],
+            &[to.r - from.r, to.g - from.g, to.b - from.b, to.a - from.a],
+        )
+    }
+}
+
+/// How the progress of a tween accelerates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+    /// Starts slowly and accelerates.
+    EaseIn,
+    /// Starts quickly and decelerates.
+    EaseOut,
+    /// Accelerates, then decelerates.
+    EaseInOut,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::EaseInOut
+    }
+}
+
+impl Easing {
+    /// Returns the eased progress for a linear progress between 0 and 1.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// A damped spring pulling a value to its target.
+///
+/// Its damping is at least a tenth of the critical damping, so it always comes to rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spring {
+    stiffness: f32,
+    damping: f32,
+    mass: f32,
+}
+
+impl Default for Spring {
+    /// A spring settling quickly with a little overshoot.
+    fn default() -> Self {
+        Self {
+            stiffness: 170.0,
+            damping: 26.0,
+            mass: 1.0,
+        }
+    }
+}
+
+impl Spring {
+    /// Creates a spring with a mass of 1.
+    ///
+    /// The stiffer the spring, the faster it moves. The lower the damping, the more it
+    /// oscillates.
+    pub fn new(stiffness: f32, damping: f32) -> Self {
+        Self {
+            stiffness: sanitize(stiffness, 170.0),
+            damping: damping.max(0.0),
+            mass: 1.0,
+        }
+    }
+
+    /// Sets the mass of the spring. A heavier spring is slower.
+    pub fn mass(self, mass: f32) -> Self {
+        Self {
+            mass: sanitize(mass, 1.0),
+            ..self
+        }
+    }
+
+    /// Returns the acceleration at `position`, with the target at 1.
+    fn acceleration(&self, position: f32, velocity: f32) -> f32 {
+        (-self.stiffness * (position - 1.0) - self.damping() * velocity) / self.mass
+    }
+
+    /// Returns the damping, raised so the oscillations fade.
+    fn damping(&self) -> f32 {
+        let critical = 2.0 * (self.stiffness * self.mass).sqrt();
+
+        self.damping.max(critical * MIN_DAMPING_RATIO)
+    }
+}
+
+/// How a value moves to its target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transition {
+    /// The value jumps to its target.
+    None,
+    /// The value reaches its target after a duration, following an easing curve.
+    Tween {
+        /// The duration of the tween.
+        duration: Duration,
+        /// The easing curve of the tween.
+        easing: Easing,
+    },
+    /// The value is pulled to its target by a spring.
+    Spring(Spring),
+}
+
+impl Transition {
+    /// Creates a tween.
+    pub fn tween(duration: Duration, easing: Easing) -> Self {
+        Transition::Tween { duration, easing }
+    }
+
+    /// Creates a spring transition.
+    pub fn spring(spring: Spring) -> Self {
+        Transition::Spring(spring)
+    }
+}
+
+/// A value moving to its target over the frames of the display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animated<T> {
+    from: T,
+    target: T,
+    transition: Transition,
+    // From 0 at `from` to 1 at `target`.
+    progress: f32,
+    // In progress per second, for springs.
+    velocity: f32,
+    elapsed: Duration,
+    is_animating: bool,
+}
+
+impl<T: Interpolate> Animated<T> {
+    /// Creates a value at rest, which moves to its next targets with the given transition.
+    pub fn new(value: T, transition: Transition) -> Self {
+        Self {
+            from: value,
+            target: value,
+            transition,
+            progress: 1.0,
+            velocity: 0.0,
+            elapsed: Duration::from_secs(0),
+            is_animating: false,
+        }
+    }
+
+    /// Returns the current value.
+    pub fn value(&self) -> T {
+        self.from.interpolate(self.target, self.progress)
+    }
+
+    /// Returns the value the animation moves to.
+    pub fn target(&self) -> T {
+        self.target
+    }
+
+    /// Returns `true` until the value reaches its target.
+    pub fn is_animating(&self) -> bool {
+        self.is_animating
+    }
+
+    /// Starts moving from the current value to `target`.
+    ///
+    /// A spring keeps its velocity, along the way to the new target.
+    pub fn set_target(&mut self, target: T) {
+        let value = self.value();
+        // The velocity is relative to the way to the target, so it's scaled to the new way.
+        self.velocity = match self.transition {
+            Transition::Spring(_) if self.is_animating => self
+                .from
+                .ratio(self.target, value, target)
+                .map_or(0.0, |ratio| self.velocity * ratio),
+            _ => 0.0,
+        };
+        self.from = value;
+        self.target = target;
+        self.progress = 0.0;
+        self.elapsed = Duration::from_secs(0);
+        self.is_animating = true;
+
+        if let Transition::None = self.transition {
+            self.finish();
+        }
+    }
+
+    /// Jumps to `value` and stops animating.
+    pub fn set(&mut self, value: T) {
+        self.from = value;
+        self.target = value;
+        self.finish();
+    }
+
+    /// Changes the transition of the next targets.
+    pub fn set_transition(&mut self, transition: Transition) {
+        self.transition = transition;
+    }
+
+    /// Advances the animation to the given frame.
+    pub fn tick(&mut self, frame: &Frame) {
+        self.advance(frame.elapsed());
+    }
+
+    /// Advances the animation by `elapsed`.
+    pub fn advance(&mut self, elapsed: Duration) {
+        if !self.is_animating {
+            return;
+        }
+
+        match self.transition {
+            Transition::None => self.finish(),
+            Transition::Tween { duration, easing } => {
+                self.elapsed += elapsed;
+                if self.elapsed >= duration {
+                    self.finish();
+                } else {
+                    let t = self.elapsed.as_secs_f32() / duration.as_secs_f32();
+                    self.progress = easing.apply(t);
+                }
+            }
+            Transition::Spring(spring) => {
+                // Fixed steps keep the result independent of the frame rate.
+                let step = SPRING_STEP.as_secs_f32();
+                self.elapsed += elapsed;
+                while self.elapsed >= SPRING_STEP {
+                    self.elapsed -= SPRING_STEP;
+                    self.velocity += spring.acceleration(self.progress, self.velocity) * step;
+                    self.progress += self.velocity * step;
+                }
+
+                let is_at_rest = (self.progress - 1.0).abs() < REST_THRESHOLD
+                    && self.velocity.abs() < REST_THRESHOLD;
+                if is_at_rest {
+                    self.finish();
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.from = self.target;
+        self.progress = 1.0;
+        self.velocity = 0.0;
+        self.elapsed = Duration::from_secs(0);
+        self.is_animating = false;
+    }
+}
+
+const SPRING_STEP: Duration = Duration::from_millis(1);
+const REST_THRESHOLD: f32 = 1e-3;
+const MIN_DAMPING_RATIO: f32 = 0.1;
+
+/// Returns the projection of `vector` on `on`, as a fraction of the length of `on`.
+fn project(vector: &[f32], on: &[f32]) -> Option<f32> {
+    let dot = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+    let ratio = dot(vector, on) / dot(on, on);
+
+    Some(ratio).filter(|ratio| ratio.is_finite())
+}
+
+fn sanitize(value: f32, default: f32) -> f32 {
+    if value.is_finite() && value > 0.0 {
+        value
+    } else {
+        default
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    /// Ticks `count` frames, `interval` apart, as the display would.
+    fn tick<T: Interpolate>(animated: &mut Animated<T>, count: u32, interval: Duration) {
+        let start = Instant::now();
+
+        for index in 1..=count {
+            animated.tick(&Frame::new(start + interval * index, interval));
+        }
+    }
+
+    /// Ticks frames at 60 fps until the value is at rest, and returns how long it took.
+    fn settle<T: Interpolate>(animated: &mut Animated<T>, timeout: Duration) -> Duration {
+        let interval = Duration::from_micros(16_667);
+        let mut elapsed = Duration::from_secs(0);
+
+        while animated.is_animating() {
+            assert!(elapsed < timeout, "still animating after {:?}", elapsed);
+            tick(animated, 1, interval);
+            elapsed += interval;
+        }
+
+        elapsed
+    }
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 1e-4,
+            "{} isn't close to {}",
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn jumps_without_transition() {
+        let mut animated = Animated::new(0.0, Transition::None);
+
+        animated.set_target(10.0);
+
+        assert!(!animated.is_animating());
+        assert_eq!(animated.value(), 10.0);
+    }
+
+    #[test]
+    fn tweens_along_easing() {
+        let transition = Transition::tween(Duration::from_millis(100), Easing::Linear);
+        let mut animated = Animated::new(0.0, transition);
+
+        animated.set_target(10.0);
+        tick(&mut animated, 5, Duration::from_millis(10));
+        assert!(animated.is_animating());
+        assert_close(animated.value(), 5.0);
+
+        tick(&mut animated, 5, Duration::from_millis(10));
+        assert!(!animated.is_animating());
+        assert_eq!(animated.value(), 10.0);
+    }
+
+    #[test]
+    fn spring_settles_on_target() {
+        let mut animated = Animated::new(0.0, Transition::spring(Spring::default()));
+
+        animated.set_target(10.0);
+        settle(&mut animated, Duration::from_secs(2));
+
+        assert_eq!(animated.value(), 10.0);
+        assert_eq!(animated.target(), 10.0);
+    }
+
+    #[test]
+    fn undamped_spring_settles() {
+        let spring = Spring::new(170.0, 0.0);
+        let mut animated = Animated::new(0.0, Transition::spring(spring));
+
+        animated.set_target(1.0);
+        settle(&mut animated, Duration::from_secs(20));
+
+        assert_eq!(animated.value(), 1.0);
+    }
+
+    #[test]
+    fn spring_ignores_frame_rate() {
+        let transition = Transition::spring(Spring::default());
+        let mut fast = Animated::new(0.0, transition);
+        let mut slow = Animated::new(0.0, transition);
+
+        fast.set_target(1.0);
+        slow.set_target(1.0);
+        tick(&mut fast, 12, Duration::from_millis(10));
+        tick(&mut slow, 3, Duration::from_millis(40));
+
+        assert_eq!(fast.value(), slow.value());
+    }
+
+    #[test]
+    fn retargeted_spring_keeps_velocity() {
+        let mut animated = Animated::new(0.0, Transition::spring(Spring::default()));
+        animated.set_target(1.0);
+        tick(&mut animated, 3, Duration::from_millis(16));
+        let value = animated.value();
+        // With a way of 1, the velocity is in units per second.
+        let velocity = animated.velocity;
+        assert!(velocity > 0.0);
+
+        animated.set_target(3.0);
+        assert_close(animated.value(), value);
+        assert_close(animated.velocity * (3.0 - value), velocity);
+
+        // Going back, the value keeps moving away from its new target at first.
+        animated.set_target(-1.0);
+        assert_close(animated.velocity * (-1.0 - value), velocity);
+        assert!(animated.velocity < 0.0);
+    }
+
+    #[test]
+    fn retargeted_spring_keeps_velocity_along_new_way() {
+        let mut animated = Animated::new(Point::ORIGIN, Transition::spring(Spring::default()));
+        animated.set_target(Point::new(1.0, 0.0));
+        tick(&mut animated, 3, Duration::from_millis(16));
+        let value = animated.value();
+        let velocity = animated.velocity;
+        let mut across = animated;
+
+        // Only the part of the velocity along the new way is kept.
+        animated.set_target(Point::new(value.x + 1.0, 1.0));
+        assert_close(animated.velocity * 2.0, velocity);
+
+        across.set_target(Point::new(value.x, 1.0));
+        assert_close(across.velocity, 0.0);
+    }
+
+    #[test]
+    fn retargeted_tween_starts_again() {
+        let transition = Transition::tween(Duration::from_millis(100), Easing::Linear);
+        let mut animated = Animated::new(0.0, transition);
+
+        animated.set_target(10.0);
+        tick(&mut animated, 5, Duration::from_millis(10));
+        animated.set_target(0.0);
+
+        assert_close(animated.value(), 5.0);
+        tick(&mut animated, 5, Duration::from_millis(10));
+        assert_close(animated.value(), 2.5);
+    }
+}
@@ -0,0 +1,444 @@
+//! Read and write the system clipboard.
+//!
+//! The clipboard is accessed from `Application::update` with the commands returned by [`write`]
+//! and [`read`]. The view performs them on the main thread against the general `NSPasteboard`.
+//!
+//! [`Memory`] implements the same [`Clipboard`] trait without touching AppKit, so the logic built
+//! on top of it can be exercised on any platform.
+//!
+//! [`write`]: fn.write.html
+//! [`read`]: fn.read.html
+//! [`Memory`]: struct.Memory.html
+//! [`Clipboard`]: trait.Clipboard.html
+
+use std::collections::HashMap;
+#[cfg(target_os = "macos")]
+use std::ffi::CStr;
+use std::path::PathBuf;
+
+#[cfg(target_os = "macos")]
+use cocoa::appkit::NSPasteboard;
+#[cfg(target_os = "macos")]
+use cocoa::base::{id, nil, BOOL, NO};
+#[cfg(target_os = "macos")]
+use cocoa::foundation::{NSArray, NSString, NSUInteger};
+
+#[cfg(target_os = "macos")]
+use objc::runtime::Object;
+#[cfg(target_os = "macos")]
+use objc::{class, msg_send, sel, sel_impl};
+
+use crate::command::{self, Command};
+#[cfg(target_os = "macos")]
+use crate::{nsdata, nsstring};
+
+/// A kind of clipboard content.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// Plain UTF-8 text.
+    Text,
+    /// Rich text in RTF.
+    Rtf,
+    /// An HTML fragment.
+    Html,
+    /// A PNG image.
+    Png,
+    /// A list of file URLs.
+    FileUrls,
+    /// Application-specific data identified by its Uniform Type Identifier, for example
+    /// `com.example.synth.preset`.
+    Custom(String),
+}
+
+impl Kind {
+    /// Returns the Uniform Type Identifier of the kind.
+    pub fn uti(&self) -> &str {
+        match self {
+            Kind::Text => "public.utf8-plain-text",
+            Kind::Rtf => "public.rtf",
+            Kind::Html => "public.html",
+            Kind::Png => "public.png",
+            Kind::FileUrls => "public.file-url",
+            Kind::Custom(uti) => uti,
+        }
+    }
+}
+
+/// A piece of clipboard content.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+    /// Plain text.
+    Text(String),
+    /// Rich text in RTF.
+    Rtf(String),
+    /// An HTML fragment.
+    Html(String),
+    /// The encoded bytes of a PNG image.
+    Png(Vec<u8>),
+    /// Paths of files.
+    FileUrls(Vec<PathBuf>),
+    /// Application-specific data.
+    Custom {
+        /// The Uniform Type Identifier of the data.
+        uti: String,
+        /// The serialized data.
+        data: Vec<u8>,
+    },
+}
+
+impl Content {
+    /// Returns the kind of the content.
+    pub fn kind(&self) -> Kind {
+        match self {
+            Content::Text(_) => Kind::Text,
+            Content::Rtf(_) => Kind::Rtf,
+            Content::Html(_) => Kind::Html,
+            Content::Png(_) => Kind::Png,
+            Content::FileUrls(_) => Kind::FileUrls,
+            Content::Custom { uti, .. } => Kind::Custom(uti.clone()),
+        }
+    }
+}
+
+/// A clipboard which can hold several representations of the same content at once.
+pub trait Clipboard {
+    /// Reads the content of the given kind, if there is any.
+    fn read(&self, kind: &Kind) -> Option<Content>;
+
+    /// Replaces the content of the clipboard with the given representations.
+    fn write(&mut self, contents: Vec<Content>);
+
+    /// Returns `true` if the clipboard holds content of the given kind.
+    fn contains(&self, kind: &Kind) -> bool {
+        self.read(kind).is_some()
+    }
+}
+
+/// A clipboard that lives in memory.
+///
+/// It isn't shared with other applications.
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    contents: HashMap<Kind, Content>,
+}
+
+impl Memory {
+    /// Creates an empty clipboard.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clipboard for Memory {
+    fn read(&self, kind: &Kind) -> Option<Content> {
+        self.contents.get(kind).cloned()
+    }
+
+    fn write(&mut self, contents: Vec<Content>) {
+        self.contents = contents.into_iter().map(|c| (c.kind(), c)).collect();
+    }
+}
+
+impl iced_native::Clipboard for Memory {
+    fn content(&self) -> Option<String> {
+        text(self)
+    }
+}
+
+/// Creates a command which replaces the content of the clipboard with the given
+/// representations.
+///
+/// Pass several representations of the same value (for example, a preset as
+/// `Content::Custom` and its name as `Content::Text`) to let each receiver pick the richest one
+/// it understands.
+pub fn write<T>(contents: Vec<Content>) -> Command<T> {
+    Action::Write(contents).into()
+}
+
+/// Creates a command which reads the content of the given kind and produces a message from it.
+///
+/// `None` is passed to `f` if the clipboard has no content of this kind.
+pub fn read<T>(kind: Kind, f: impl Fn(Option<Content>) -> T + 'static + Send) -> Command<T> {
+    Action::Read(kind, Box::new(f)).into()
+}
+
+/// A clipboard operation requested by the application.
+pub(crate) enum Action<T> {
+    Write(Vec<Content>),
+    Read(Kind, Box<dyn Fn(Option<Content>) -> T + Send>),
+}
+
+impl<T> Action<T> {
+    /// Performs the action against the clipboard, returning the message it produces, if any.
+    #[cfg(any(target_os = "macos", test))]
+    pub(crate) fn perform(self, clipboard: &mut dyn Clipboard) -> Option<T> {
+        match self {
+            Action::Write(contents) => {
+                clipboard.write(contents);
+                None
+            }
+            Action::Read(kind, f) => Some(f(clipboard.read(&kind))),
+        }
+    }
+
+    pub(crate) fn map<A>(self, f: std::sync::Arc<dyn Fn(T) -> A + Send + Sync>) -> Action<A>
+    where
+        T: 'static,
+        A: 'static,
+    {
+        match self {
+            Action::Write(contents) => Action::Write(contents),
+            Action::Read(kind, read) => Action::Read(kind, Box::new(move |c| f(read(c)))),
+        }
+    }
+}
+
+impl<T> From<Action<T>> for Command<T> {
+    fn from(action: Action<T>) -> Self {
+        command::Action::Clipboard(action).into()
+    }
+}
+
+fn text(clipboard: &dyn Clipboard) -> Option<String> {
+    match clipboard.read(&Kind::Text) {
+        Some(Content::Text(text)) => Some(text),
+        _ => None,
+    }
+}
+
+/// The general `NSPasteboard`.
+#[cfg(target_os = "macos")]
+pub(crate) struct Pasteboard {
+    object: id,
+}
+
+#[cfg(target_os = "macos")]
+impl Pasteboard {
+    pub(crate) fn new() -> Self {
+        let object = unsafe { NSPasteboard::generalPasteboard(nil) };
+
+        Self { object }
+    }
+
+    /// Wraps another pasteboard, like the one of a dragging session.
+    pub(crate) unsafe fn from_raw(object: id) -> Self {
+        Self { object }
+    }
+
+    unsafe fn read_string(&self, kind: &Kind) -> Option<String> {
+        let string: id = msg_send![self.object, stringForType: nsstring(kind.uti())];
+        if string.is_null() {
+            return None;
+        }
+        let ptr = NSString::UTF8String(string);
+        if ptr.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(ptr).to_string_lossy().to_string())
+        }
+    }
+
+    unsafe fn read_data(&self, kind: &Kind) -> Option<Vec<u8>> {
+        let data: id = msg_send![self.object, dataForType: nsstring(kind.uti())];
+        if data.is_null() {
+            return None;
+        }
+        let bytes: *const u8 = msg_send![data, bytes];
+        let length: NSUInteger = msg_send![data, length];
+        if bytes.is_null() {
+            Some(Vec::new())
+        } else {
+            Some(std::slice::from_raw_parts(bytes, length as usize).to_vec())
+        }
+    }
+
+    unsafe fn read_file_urls(&self) -> Option<Vec<PathBuf>> {
+        let class = class!(NSURL);
+        let class_ref: *mut Object = msg_send![class, self];
+        let classes = NSArray::arrayWithObject(nil, class_ref);
+        let items = self.object.readObjectsForClasses_options(classes, nil);
+        if items.is_null() || items.count() == 0 {
+            return None;
+        }
+        let paths = (0..items.count())
+            .map(|n| items.objectAtIndex(n))
+            .filter(|url| {
+                let is_file: BOOL = msg_send![*url, isFileURL];
+                is_file != NO
+            })
+            .map(|url| crate::pathbuf_from_nsurl(url))
+            .collect::<Vec<_>>();
+
+        if paths.is_empty() {
+            None
+        } else {
+            Some(paths)
+        }
+    }
+
+    unsafe fn write_file_urls(&self, paths: &[PathBuf]) {
+        let urls = paths
+            .iter()
+            .map(|path| {
+                let url: id = msg_send![
+                    class!(NSURL),
+                    fileURLWithPath: nsstring(&path.to_string_lossy())
+                ];
+                url
+            })
+            .collect::<Vec<_>>();
+        let array = NSArray::arrayWithObjects(nil, &urls);
+        let _: BOOL = msg_send![self.object, writeObjects: array];
+    }
+
+    unsafe fn write_string(&self, kind: &Kind, string: &str) {
+        let _: BOOL = msg_send![
+            self.object,
+            setString: nsstring(string)
+            forType: nsstring(kind.uti())
+        ];
+    }
+
+    unsafe fn write_data(&self, kind: &Kind, data: &[u8]) {
+        let _: BOOL = msg_send![self.object, setData: nsdata(data) forType: nsstring(kind.uti())];
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Clipboard for Pasteboard {
+    fn read(&self, kind: &Kind) -> Option<Content> {
+        unsafe {
+            match kind {
+                Kind::Text => self.read_string(kind).map(Content::Text),
+                Kind::Rtf => self.read_string(kind).map(Content::Rtf),
+                Kind::Html => self.read_string(kind).map(Content::Html),
+                Kind::Png => self.read_data(kind).map(Content::Png),
+                Kind::FileUrls => self.read_file_urls().map(Content::FileUrls),
+                Kind::Custom(uti) => self.read_data(kind).map(|data| Content::Custom {
+                    uti: uti.clone(),
+                    data,
+                }),
+            }
+        }
+    }
+
+    fn write(&mut self, contents: Vec<Content>) {
+        unsafe {
+            self.object.clearContents();
+
+            // `writeObjects:` creates new pasteboard items, so the URLs go first and the other
+            // representations are attached to the first item afterwards.
+            contents.iter().for_each(|content| {
+                if let Content::FileUrls(paths) = content {
+                    self.write_file_urls(paths);
+                }
+            });
+
+            contents.iter().for_each(|content| match content {
+                Content::Text(string) | Content::Rtf(string) | Content::Html(string) => {
+                    self.write_string(&content.kind(), string)
+                }
+                Content::Png(data) | Content::Custom { data, .. } => {
+                    self.write_data(&content.kind(), data)
+                }
+                Content::FileUrls(_) => (),
+            });
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl iced_native::Clipboard for Pasteboard {
+    fn content(&self) -> Option<String> {
+        text(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents() -> Vec<Content> {
+        vec![
+            Content::Text(String::from("Pad")),
+            Content::Rtf(String::from("{\\rtf1 Pad}")),
+            Content::Html(String::from("<b>Pad</b>")),
+            Content::Png(vec![0x89, b'P', b'N', b'G']),
+            Content::FileUrls(vec![PathBuf::from("/tmp/pad.preset")]),
+            Content::Custom {
+                uti: String::from("com.example.synth.preset"),
+                data: vec![1, 2, 3],
+            },
+        ]
+    }
+
+    #[test]
+    fn reads_every_kind_written() {
+        let mut memory = Memory::new();
+        memory.write(contents());
+
+        for content in contents() {
+            assert!(memory.contains(&content.kind()));
+            assert_eq!(memory.read(&content.kind()), Some(content));
+        }
+    }
+
+    #[test]
+    fn reads_nothing_when_empty() {
+        let memory = Memory::new();
+
+        for content in contents() {
+            assert!(!memory.contains(&content.kind()));
+            assert_eq!(memory.read(&content.kind()), None);
+        }
+    }
+
+    #[test]
+    fn write_replaces_every_representation() {
+        let mut memory = Memory::new();
+        memory.write(contents());
+        memory.write(vec![Content::Html(String::from("<i>Lead</i>"))]);
+
+        assert_eq!(
+            memory.read(&Kind::Html),
+            Some(Content::Html(String::from("<i>Lead</i>")))
+        );
+        assert!(!memory.contains(&Kind::Text));
+        assert!(!memory.contains(&Kind::Png));
+    }
+
+    #[test]
+    fn custom_kinds_are_told_apart_by_uti() {
+        let mut memory = Memory::new();
+        memory.write(contents());
+
+        assert!(memory.contains(&Kind::Custom(String::from("com.example.synth.preset"))));
+        assert!(!memory.contains(&Kind::Custom(String::from("com.example.synth.patch"))));
+    }
+
+    #[test]
+    fn text_is_shared_with_widgets() {
+        let mut memory = Memory::new();
+        assert_eq!(iced_native::Clipboard::content(&memory), None);
+
+        memory.write(contents());
+        assert_eq!(
+            iced_native::Clipboard::content(&memory),
+            Some(String::from("Pad"))
+        );
+    }
+
+    #[test]
+    fn actions_perform_against_the_clipboard() {
+        let mut memory = Memory::new();
+
+        let write: Action<()> = Action::Write(contents());
+        assert_eq!(write.perform(&mut memory), None);
+
+        let read = Action::Read(Kind::Png, Box::new(|content: Option<Content>| content));
+        assert_eq!(
+            read.perform(&mut memory),
+            Some(Some(Content::Png(vec![0x89, b'P', b'N', b'G'])))
+        );
+    }
+}
@@ -0,0 +1,423 @@
+//! Schedule animation frames in step with the display.
+//!
+//! While `Application::is_animating` returns `true`, or after the application requests a
+//! frame with [`request_animation_frame`], a `CVDisplayLink` ticks on every refresh of the
+//! display showing the view. Each tick, down to `Settings::max_frame_rate`, calls
+//! `Application::on_frame` and renders a frame if something changed. The display link stops
+//! as soon as no frame is needed.
+//!
+//! The clock reads the time from a [`TimeSource`], so it can be driven by a fake one.
+//!
+//! [`request_animation_frame`]: fn.request_animation_frame.html
+//! [`TimeSource`]: trait.TimeSource.html
+
+#[cfg(target_os = "macos")]
+use std::ffi::c_void;
+#[cfg(target_os = "macos")]
+use std::mem::ManuallyDrop;
+#[cfg(target_os = "macos")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(target_os = "macos")]
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{command, Command};
+#[cfg(target_os = "macos")]
+use crate::{main_thread, Object};
+
+/// A frame of the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    timestamp: Instant,
+    elapsed: Duration,
+}
+
+impl Frame {
+    /// Creates a frame happening at `timestamp`, `elapsed` after the previous one.
+    pub fn new(timestamp: Instant, elapsed: Duration) -> Self {
+        Self { timestamp, elapsed }
+    }
+
+    /// Returns when the frame happens.
+    pub fn timestamp(&self) -> Instant {
+        self.timestamp
+    }
+
+    /// Returns the time since the previous frame.
+    ///
+    /// It's zero for the first frame after the clock was idle, so animations don't jump.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Creates a `Command` calling `Application::on_frame` on the next frame of the display.
+pub fn request_animation_frame<T>() -> Command<T> {
+    command::Action::AnimationFrame.into()
+}
+
+/// Tells the time to a clock.
+#[cfg(any(target_os = "macos", test))]
+pub(crate) trait TimeSource {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// The monotonic time of the system.
+#[cfg(target_os = "macos")]
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct System;
+
+#[cfg(target_os = "macos")]
+impl TimeSource for System {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Decides which ticks of the display become frames.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug, Clone)]
+pub(crate) struct Clock<S> {
+    source: S,
+    // The shortest time between two frames.
+    interval: Duration,
+    previous: Option<Instant>,
+    is_requested: bool,
+}
+
+#[cfg(target_os = "macos")]
+impl Clock<System> {
+    /// Creates a clock delivering at most `max_frame_rate` frames per second.
+    ///
+    /// Without a limit, every tick becomes a frame.
+    pub(crate) fn new(max_frame_rate: Option<u32>) -> Self {
+        Self::with_source(max_frame_rate, System)
+    }
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl<S: TimeSource> Clock<S> {
+    /// Creates a clock reading the time from `source`.
+    pub(crate) fn with_source(max_frame_rate: Option<u32>, source: S) -> Self {
+        let interval = match max_frame_rate {
+            Some(rate) if rate > 0 => Duration::from_secs(1) / rate,
+            _ => Duration::from_secs(0),
+        };
+
+        Self {
+            source,
+            interval,
+            previous: None,
+            is_requested: false,
+        }
+    }
+
+    /// Returns the current time of the source.
+    pub(crate) fn now(&self) -> Instant {
+        self.source.now()
+    }
+
+    /// Makes the next tick deliver a frame, even if the application doesn't animate.
+    pub(crate) fn request(&mut self) {
+        self.is_requested = true;
+    }
+
+    /// Returns `true` if the clock needs ticks.
+    pub(crate) fn is_running(&self, is_animating: bool) -> bool {
+        is_animating || self.is_requested
+    }
+
+    /// Returns the frame of a tick happening now, if one is due.
+    pub(crate) fn tick(&mut self, is_animating: bool) -> Option<Frame> {
+        let now = self.now();
+        if !self.is_running(is_animating) {
+            self.previous = None;
+            return None;
+        }
+
+        let elapsed = match self.previous {
+            Some(previous) => {
+                let elapsed = now.saturating_duration_since(previous);
+                // Ticks jitter, so a tick slightly early still makes the frame.
+                if elapsed < self.interval * 7 / 8 {
+                    return None;
+                }
+                elapsed
+            }
+            None => Duration::from_secs(0),
+        };
+
+        self.previous = Some(now);
+        self.is_requested = false;
+
+        Some(Frame::new(now, elapsed))
+    }
+}
+
+/// Ticks on the main thread on every refresh of the display, while it's started.
+#[cfg(target_os = "macos")]
+pub(crate) struct DisplayLink {
+    link: CVDisplayLinkRef,
+    shared: *const Shared,
+    display: Option<CGDirectDisplayID>,
+    is_started: bool,
+}
+
+#[cfg(target_os = "macos")]
+struct Shared {
+    view: usize,
+    on_tick: unsafe fn(*mut Object),
+    // Whether a tick waits for the main thread, so ticks don't pile up when it's busy.
+    is_pending: AtomicBool,
+    // Only accessed on the main thread. Ticks already dispatched are ignored once it's set.
+    is_stopped: AtomicBool,
+}
+
+#[cfg(target_os = "macos")]
+impl DisplayLink {
+    /// Creates a stopped display link calling `on_tick` with `view` on the main thread.
+    ///
+    /// Returns `None` if the system can't create one.
+    pub(crate) fn new(view: *mut Object, on_tick: unsafe fn(*mut Object)) -> Option<Self> {
+        let mut link: CVDisplayLinkRef = std::ptr::null_mut();
+        unsafe {
+            if CVDisplayLinkCreateWithActiveCGDisplays(&mut link) != K_CV_RETURN_SUCCESS {
+                return None;
+            }
+        }
+
+        let shared = Arc::into_raw(Arc::new(Shared {
+            view: view as usize,
+            on_tick,
+            is_pending: AtomicBool::new(false),
+            is_stopped: AtomicBool::new(false),
+        }));
+        unsafe {
+            CVDisplayLinkSetOutputCallback(link, output, shared as *mut c_void);
+        }
+
+        Some(Self {
+            link,
+            shared,
+            display: None,
+            is_started: false,
+        })
+    }
+
+    /// Follows the refresh of the given display.
+    pub(crate) fn set_display(&mut self, display: CGDirectDisplayID) {
+        if self.display == Some(display) {
+            return;
+        }
+
+        self.display = Some(display);
+        unsafe {
+            CVDisplayLinkSetCurrentCGDisplay(self.link, display);
+        }
+    }
+
+    pub(crate) fn start(&mut self) {
+        if !self.is_started {
+            self.is_started = unsafe { CVDisplayLinkStart(self.link) } == K_CV_RETURN_SUCCESS;
+        }
+    }
+
+    pub(crate) fn stop(&mut self) {
+        if self.is_started {
+            unsafe {
+                CVDisplayLinkStop(self.link);
+            }
+            self.is_started = false;
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for DisplayLink {
+    fn drop(&mut self) {
+        self.stop();
+        unsafe {
+            // The callback won't run anymore, but ticks may wait for the main thread.
+            (*self.shared).is_stopped.store(true, Ordering::Release);
+            CVDisplayLinkRelease(self.link);
+            drop(Arc::from_raw(self.shared));
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl std::fmt::Debug for DisplayLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DisplayLink")
+            .field("display", &self.display)
+            .field("is_started", &self.is_started)
+            .finish()
+    }
+}
+
+/// Runs on the thread of the display link.
+#[cfg(target_os = "macos")]
+extern "C" fn output(
+    _link: CVDisplayLinkRef,
+    _now: *const c_void,
+    _output_time: *const c_void,
+    _flags_in: u64,
+    _flags_out: *mut u64,
+    context: *mut c_void,
+) -> CVReturn {
+    let shared = ManuallyDrop::new(unsafe { Arc::from_raw(context as *const Shared) });
+    if shared.is_pending.swap(true, Ordering::AcqRel) {
+        return K_CV_RETURN_SUCCESS;
+    }
+
+    let shared = Arc::clone(&shared);
+    main_thread::run(move |_| {
+        shared.is_pending.store(false, Ordering::Release);
+        if !shared.is_stopped.load(Ordering::Acquire) {
+            unsafe { (shared.on_tick)(shared.view as *mut Object) }
+        }
+    });
+
+    K_CV_RETURN_SUCCESS
+}
+
+#[cfg(target_os = "macos")]
+type CVDisplayLinkRef = *mut c_void;
+#[cfg(target_os = "macos")]
+type CVReturn = i32;
+#[cfg(target_os = "macos")]
+pub(crate) type CGDirectDisplayID = u32;
+
+#[cfg(target_os = "macos")]
+const K_CV_RETURN_SUCCESS: CVReturn = 0;
+
+#[cfg(target_os = "macos")]
+type CVDisplayLinkOutputCallback = extern "C" fn(
+    CVDisplayLinkRef,
+    *const c_void,
+    *const c_void,
+    u64,
+    *mut u64,
+    *mut c_void,
+) -> CVReturn;
+
+#[cfg(target_os = "macos")]
+#[link(name = "CoreVideo", kind = "framework")]
+extern "C" {
+    fn CVDisplayLinkCreateWithActiveCGDisplays(link: *mut CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkSetOutputCallback(
+        link: CVDisplayLinkRef,
+        callback: CVDisplayLinkOutputCallback,
+        context: *mut c_void,
+    ) -> CVReturn;
+    fn CVDisplayLinkSetCurrentCGDisplay(
+        link: CVDisplayLinkRef,
+        display: CGDirectDisplayID,
+    ) -> CVReturn;
+    fn CVDisplayLinkStart(link: CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkStop(link: CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkRelease(link: CVDisplayLinkRef);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// A time source which only moves when told to.
+    #[derive(Debug, Clone)]
+    struct Fake(Rc<Cell<Instant>>);
+
+    impl Fake {
+        fn new() -> Self {
+            Self(Rc::new(Cell::new(Instant::now())))
+        }
+
+        fn advance(&self, milliseconds: u64) {
+            self.0
+                .set(self.0.get() + Duration::from_millis(milliseconds));
+        }
+    }
+
+    impl TimeSource for Fake {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+    }
+
+    fn clock(max_frame_rate: Option<u32>) -> (Clock<Fake>, Fake) {
+        let fake = Fake::new();
+
+        (Clock::with_source(max_frame_rate, fake.clone()), fake)
+    }
+
+    fn elapsed(frame: Option<Frame>) -> Option<u64> {
+        frame.map(|frame| frame.elapsed().as_millis() as u64)
+    }
+
+    #[test]
+    fn delivers_every_tick_without_limit() {
+        let (mut clock, time) = clock(None);
+
+        assert_eq!(elapsed(clock.tick(true)), Some(0));
+        time.advance(4);
+        assert_eq!(elapsed(clock.tick(true)), Some(4));
+        time.advance(16);
+        let frame = clock.tick(true).unwrap();
+        assert_eq!(frame.timestamp(), time.now());
+        assert_eq!(frame.elapsed(), Duration::from_millis(16));
+    }
+
+    #[test]
+    fn throttles_to_max_frame_rate() {
+        let (mut clock, time) = clock(Some(30));
+
+        assert_eq!(elapsed(clock.tick(true)), Some(0));
+        time.advance(16);
+        assert_eq!(elapsed(clock.tick(true)), None);
+        time.advance(17);
+        assert_eq!(elapsed(clock.tick(true)), Some(33));
+        // A tick slightly early still makes the frame.
+        time.advance(30);
+        assert_eq!(elapsed(clock.tick(true)), Some(30));
+    }
+
+    #[test]
+    fn ignores_zero_frame_rate() {
+        let (mut clock, time) = clock(Some(0));
+
+        assert!(clock.tick(true).is_some());
+        time.advance(1);
+        assert!(clock.tick(true).is_some());
+    }
+
+    #[test]
+    fn stops_when_not_animating() {
+        let (mut clock, time) = clock(None);
+        assert!(!clock.is_running(false));
+
+        assert!(clock.tick(true).is_some());
+        time.advance(16);
+        assert!(clock.tick(false).is_none());
+
+        // The first frame after the clock was idle doesn't jump.
+        time.advance(500);
+        assert_eq!(elapsed(clock.tick(true)), Some(0));
+    }
+
+    #[test]
+    fn delivers_requested_frame_once() {
+        let (mut clock, time) = clock(None);
+
+        clock.request();
+        assert!(clock.is_running(false));
+        assert!(clock.tick(false).is_some());
+
+        time.advance(16);
+        assert!(!clock.is_running(false));
+        assert!(clock.tick(false).is_none());
+    }
+}
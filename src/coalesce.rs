@@ -0,0 +1,312 @@
+//! Merge pointer events arriving faster than frames.
+//!
+//! A fast drag sends dozens of mouse moves per frame, and each of them would run
+//! `Application::update`. Consecutive cursor moves collapse into the last one, and consecutive
+//! scrolls of the same kind add up. They wait for the next frame, unless another event
+//! arrives first: every pending event is then handled in order, so presses and releases
+//! always happen at the position the cursor had.
+//!
+//! Cursor moves are never held while the left button is down and the cursor hasn't travelled
+//! the drag threshold yet: the move starting a drag is handled while the `NSEvent` it came from
+//! is the current one, which the drag session starts from.
+
+#[cfg(any(target_os = "macos", test))]
+use iced_native::Event;
+
+#[cfg(any(target_os = "macos", test))]
+use crate::{drag, mouse, Point};
+
+/// Which pointer events are merged between frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coalescing {
+    /// Whether consecutive cursor moves collapse into the last one.
+    pub cursor_moves: bool,
+    /// Whether consecutive scrolls add up.
+    pub scrolls: bool,
+}
+
+impl Coalescing {
+    /// Merges nothing, so every event is handled as soon as it arrives.
+    pub fn none() -> Self {
+        Self {
+            cursor_moves: false,
+            scrolls: false,
+        }
+    }
+
+    /// Merges cursor moves and scrolls.
+    pub fn all() -> Self {
+        Self {
+            cursor_moves: true,
+            scrolls: true,
+        }
+    }
+}
+
+impl Default for Coalescing {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// How many events the view received, and how many of them were merged into others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Statistics {
+    received: u64,
+    merged: u64,
+}
+
+impl Statistics {
+    /// Returns the number of events received.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Returns the number of events merged into a later one.
+    pub fn merged(&self) -> u64 {
+        self.merged
+    }
+
+    /// Returns the number of events handled by the application.
+    pub fn delivered(&self) -> u64 {
+        self.received - self.merged
+    }
+}
+
+/// Holds the events which can wait for the next frame.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug)]
+pub(crate) struct Coalescer {
+    coalescing: Coalescing,
+    pending: Vec<Event>,
+    statistics: Statistics,
+    // The last position of the cursor, if it moved over the view.
+    cursor: Option<Point>,
+    press: Press,
+}
+
+/// The left button, until a drag may have started.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Press {
+    /// The button is up, or the cursor travelled the drag threshold since it was pressed.
+    None,
+    /// The button is down, pressed at the position of the cursor, if it was known.
+    Held(Option<Point>),
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl Coalescer {
+    pub(crate) fn new(coalescing: Coalescing) -> Self {
+        Self {
+            coalescing,
+            pending: Vec::new(),
+            statistics: Statistics::default(),
+            cursor: None,
+            press: Press::None,
+        }
+    }
+
+    /// Adds an event after the pending ones, merging it into the last one if possible.
+    ///
+    /// Returns `true` if the pending events must be handled now, or `false` if they can wait
+    /// for the next frame.
+    pub(crate) fn push(&mut self, event: Event) -> bool {
+        self.statistics.received += 1;
+        let is_deferred = self.is_deferred(&event);
+
+        let is_merged = match self.pending.last_mut() {
+            Some(last) => merge(self.coalescing, last, &event),
+            None => false,
+        };
+        if is_merged {
+            self.statistics.merged += 1;
+        } else {
+            self.pending.push(event);
+        }
+
+        !is_deferred
+    }
+
+    /// Returns the pending events, in order.
+    pub(crate) fn take(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.pending)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub(crate) fn statistics(&self) -> Statistics {
+        self.statistics
+    }
+
+    fn is_deferred(&mut self, event: &Event) -> bool {
+        match event {
+            Event::Mouse(mouse::Event::CursorMoved { x, y }) => {
+                let position = Point::new(*x, *y);
+                self.cursor = Some(position);
+
+                match self.press {
+                    Press::None => self.coalescing.cursor_moves,
+                    Press::Held(None) => {
+                        self.press = Press::Held(Some(position));
+                        false
+                    }
+                    Press::Held(Some(origin)) => {
+                        if distance(origin, position) > drag::THRESHOLD {
+                            self.press = Press::None;
+                        }
+                        false
+                    }
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                self.press = Press::Held(self.cursor);
+                false
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                self.press = Press::None;
+                false
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { .. }) => self.coalescing.scrolls,
+            _ => false,
+        }
+    }
+}
+
+/// Merges `event` into `last`, and returns `true` if it could.
+#[cfg(any(target_os = "macos", test))]
+fn merge(coalescing: Coalescing, last: &mut Event, event: &Event) -> bool {
+    match (last, event) {
+        (
+            Event::Mouse(mouse::Event::CursorMoved { x, y }),
+            Event::Mouse(mouse::Event::CursorMoved { x: new_x, y: new_y }),
+        ) if coalescing.cursor_moves => {
+            *x = *new_x;
+            *y = *new_y;
+            true
+        }
+        (
+            Event::Mouse(mouse::Event::WheelScrolled { delta }),
+            Event::Mouse(mouse::Event::WheelScrolled { delta: new_delta }),
+        ) if coalescing.scrolls => match (delta, new_delta) {
+            (
+                mouse::ScrollDelta::Lines { x, y },
+                mouse::ScrollDelta::Lines { x: new_x, y: new_y },
+            )
+            | (
+                mouse::ScrollDelta::Pixels { x, y },
+                mouse::ScrollDelta::Pixels { x: new_x, y: new_y },
+            ) => {
+                *x += *new_x;
+                *y += *new_y;
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn distance(a: Point, b: Point) -> f32 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moved(x: f32) -> Event {
+        Event::Mouse(mouse::Event::CursorMoved { x, y: 0.0 })
+    }
+
+    fn scrolled(delta: mouse::ScrollDelta) -> Event {
+        Event::Mouse(mouse::Event::WheelScrolled { delta })
+    }
+
+    fn pressed() -> Event {
+        Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+    }
+
+    fn released() -> Event {
+        Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+    }
+
+    #[test]
+    fn collapses_cursor_moves() {
+        let mut coalescer = Coalescer::new(Coalescing::all());
+
+        assert!(!coalescer.push(moved(1.0)));
+        assert!(!coalescer.push(moved(2.0)));
+        assert!(!coalescer.push(moved(3.0)));
+
+        assert_eq!(coalescer.take(), vec![moved(3.0)]);
+        assert!(coalescer.is_empty());
+        assert_eq!(coalescer.statistics().received(), 3);
+        assert_eq!(coalescer.statistics().merged(), 2);
+        assert_eq!(coalescer.statistics().delivered(), 1);
+    }
+
+    #[test]
+    fn adds_up_scrolls_of_same_kind() {
+        let mut coalescer = Coalescer::new(Coalescing::all());
+        let lines = |y| scrolled(mouse::ScrollDelta::Lines { x: 0.0, y });
+        let pixels = |y| scrolled(mouse::ScrollDelta::Pixels { x: 0.0, y });
+
+        assert!(!coalescer.push(lines(1.0)));
+        assert!(!coalescer.push(lines(2.0)));
+        assert!(!coalescer.push(pixels(10.0)));
+        assert!(!coalescer.push(pixels(5.0)));
+
+        assert_eq!(coalescer.take(), vec![lines(3.0), pixels(15.0)]);
+    }
+
+    #[test]
+    fn keeps_presses_in_order() {
+        let mut coalescer = Coalescer::new(Coalescing::all());
+
+        assert!(!coalescer.push(moved(1.0)));
+        assert!(coalescer.push(pressed()));
+
+        assert_eq!(coalescer.take(), vec![moved(1.0), pressed()]);
+    }
+
+    #[test]
+    fn merges_nothing_without_coalescing() {
+        let mut coalescer = Coalescer::new(Coalescing::none());
+
+        assert!(coalescer.push(moved(1.0)));
+        assert!(coalescer.push(moved(2.0)));
+
+        assert_eq!(coalescer.take(), vec![moved(1.0), moved(2.0)]);
+        assert_eq!(coalescer.statistics().merged(), 0);
+    }
+
+    #[test]
+    fn handles_moves_until_drag_threshold_right_away() {
+        let mut coalescer = Coalescer::new(Coalescing::all());
+
+        assert!(!coalescer.push(moved(0.0)));
+        assert!(coalescer.push(pressed()));
+        assert!(coalescer.push(moved(2.0)));
+        // This move may start a drag.
+        assert!(coalescer.push(moved(5.0)));
+        assert!(!coalescer.push(moved(6.0)));
+
+        assert!(coalescer.push(released()));
+        assert!(!coalescer.push(moved(7.0)));
+    }
+
+    #[test]
+    fn measures_drag_threshold_from_first_move_of_unknown_press() {
+        let mut coalescer = Coalescer::new(Coalescing::all());
+
+        assert!(coalescer.push(pressed()));
+        assert!(coalescer.push(moved(10.0)));
+        assert!(coalescer.push(moved(14.0)));
+        assert!(coalescer.push(moved(15.0)));
+        assert!(!coalescer.push(moved(16.0)));
+    }
+}
@@ -0,0 +1,123 @@
+//! Operations requested by the application.
+
+use std::sync::Arc;
+
+use crate::futures::Future;
+use crate::{clipboard, drag, Size};
+
+/// A set of operations to be performed after `Application::update`.
+///
+/// Besides the asynchronous operations of an Iced command, it carries requests to the view
+/// itself, like writing to the clipboard or starting a drag.
+pub struct Command<T> {
+    futures: iced_native::Command<T>,
+    actions: Vec<Action<T>>,
+}
+
+/// A request to the view, performed on the main thread.
+pub(crate) enum Action<T> {
+    Clipboard(clipboard::Action<T>),
+    Drag(drag::Action<T>),
+    Resize(Size),
+    Redraw,
+    AnimationFrame,
+}
+
+impl<T> Command<T> {
+    /// Creates an empty `Command`.
+    ///
+    /// In other words, a `Command` that does nothing.
+    pub fn none() -> Self {
+        Self {
+            futures: iced_native::Command::none(),
+            actions: Vec::new(),
+        }
+    }
+
+    /// Creates a `Command` that performs the action of the given future.
+    pub fn perform<A>(
+        future: impl Future<Output = T> + 'static + Send,
+        f: impl Fn(T) -> A + 'static + Send,
+    ) -> Command<A> {
+        iced_native::Command::perform(future, f).into()
+    }
+
+    /// Creates a `Command` that performs the actions of all the given commands.
+    pub fn batch(commands: impl IntoIterator<Item = Command<T>>) -> Self {
+        let (futures, actions): (Vec<_>, Vec<_>) = commands
+            .into_iter()
+            .map(|command| (command.futures, command.actions))
+            .unzip();
+
+        Self {
+            futures: iced_native::Command::batch(futures),
+            actions: actions.into_iter().flatten().collect(),
+        }
+    }
+
+    /// Applies a transformation to the messages produced by the `Command`.
+    pub fn map<A>(self, f: impl Fn(T) -> A + 'static + Send + Sync) -> Command<A>
+    where
+        T: 'static,
+        A: 'static,
+    {
+        let f: Arc<dyn Fn(T) -> A + Send + Sync> = Arc::new(f);
+        let futures_f = f.clone();
+
+        Command {
+            futures: self.futures.map(move |message| futures_f(message)),
+            actions: self
+                .actions
+                .into_iter()
+                .map(|action| action.map(f.clone()))
+                .collect(),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub(crate) fn into_parts(self) -> (iced_native::Command<T>, Vec<Action<T>>) {
+        (self.futures, self.actions)
+    }
+}
+
+impl<T> Action<T> {
+    fn map<A>(self, f: Arc<dyn Fn(T) -> A + Send + Sync>) -> Action<A>
+    where
+        T: 'static,
+        A: 'static,
+    {
+        match self {
+            Action::Clipboard(action) => Action::Clipboard(action.map(f)),
+            Action::Drag(action) => Action::Drag(action.map(f)),
+            Action::Resize(size) => Action::Resize(size),
+            Action::Redraw => Action::Redraw,
+            Action::AnimationFrame => Action::AnimationFrame,
+        }
+    }
+}
+
+impl<T> From<Action<T>> for Command<T> {
+    fn from(action: Action<T>) -> Self {
+        Self {
+            futures: iced_native::Command::none(),
+            actions: vec![action],
+        }
+    }
+}
+
+impl<T> From<iced_native::Command<T>> for Command<T> {
+    fn from(futures: iced_native::Command<T>) -> Self {
+        Self {
+            futures,
+            actions: Vec::new(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Command<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Command")
+            .field("actions", &self.actions.len())
+            .finish()
+    }
+}
@@ -0,0 +1,427 @@
+//! Share the GPU device and the caches of the renderer between views.
+//!
+//! Each view renders to its own surface and swap chain, but the device, its queue and the
+//! renderer, with its pipelines and its glyph and image caches, can be shared by the views of
+//! the process which set `Settings::share_context`. The shared context is created with the
+//! first of these views and torn down with the last one.
+
+#[cfg(target_os = "macos")]
+use std::cell::RefCell;
+#[cfg(target_os = "macos")]
+use std::ffi::c_void;
+#[cfg(any(target_os = "macos", test))]
+use std::rc::{Rc, Weak};
+
+#[cfg(target_os = "macos")]
+use iced_wgpu::{wgpu, Backend, Primitive, Renderer, Settings as RendererSettings, Viewport};
+
+#[cfg(target_os = "macos")]
+use objc::{msg_send, sel, sel_impl};
+
+#[cfg(any(target_os = "macos", test))]
+use crate::{gpu, Settings};
+#[cfg(target_os = "macos")]
+use crate::{init, mouse, Color, Error, Geometry, Object};
+
+#[cfg(target_os = "macos")]
+thread_local! {
+    // Views only live on the main thread, so their shared contexts do too.
+    static SHARED: RefCell<Registry<Key, RefCell<Context>>> = RefCell::new(Registry::new());
+}
+
+/// The device of the views and their renderer.
+#[cfg(target_os = "macos")]
+pub(crate) struct Context {
+    pub(crate) device: wgpu::Device,
+    pub(crate) queue: wgpu::Queue,
+    pub(crate) renderer: Renderer,
+    settings: Settings,
+    // Incremented whenever the device is recreated, so views know their swap chain is stale.
+    generation: u64,
+}
+
+#[cfg(target_os = "macos")]
+impl Context {
+    /// Returns the context shared by the views with these settings, if the settings allow it
+    /// and a view already created it.
+    pub(crate) fn shared(settings: &Settings) -> Option<Rc<RefCell<Self>>> {
+        if !settings.share_context {
+            return None;
+        }
+
+        let key = Key::new(settings);
+        SHARED.with(|shared| shared.borrow_mut().get(&key))
+    }
+
+    /// Returns the context of a new view, shared with the views created later if the settings
+    /// allow it.
+    ///
+    /// If another view shared its context in the meantime, that one is returned instead.
+    pub(crate) fn into_shared(self, settings: &Settings) -> Rc<RefCell<Self>> {
+        if !settings.share_context {
+            return Rc::new(RefCell::new(self));
+        }
+
+        let key = Key::new(settings);
+        SHARED.with(|shared| {
+            shared
+                .borrow_mut()
+                .get_or_insert_with(key, || RefCell::new(self))
+        })
+    }
+
+    /// Creates a context of its own, whose adapter is chosen for `surface`.
+    pub(crate) fn new(surface: &wgpu::Surface, settings: Settings) -> Result<Self, Error> {
+        let (mut device, queue) =
+            init_device_and_queue(surface, settings.power_preference, settings.backend)?;
+        check_surface(&device, surface, &settings)?;
+        let renderer = Renderer::new(Backend::new(
+            &mut device,
+            RendererSettings::from(settings.clone()),
+        ));
+
+        Ok(Self {
+            device,
+            queue,
+            renderer,
+            settings,
+            generation: 0,
+        })
+    }
+
+    /// Replaces the device and the renderer with new ones, e.g. after the device was lost.
+    ///
+    /// The adapter is chosen for `surface` again, blocking the thread, so the main thread
+    /// creates its new context with a `Provider` and hands it to `replace` instead. On error,
+    /// the context is left as it was.
+    pub(crate) fn recreate(&mut self, surface: &wgpu::Surface) -> Result<(), Error> {
+        let context = Self::new(surface, self.settings.clone())?;
+        self.replace(context);
+
+        Ok(())
+    }
+
+    /// Replaces the device and the renderer with those of a new context.
+    ///
+    /// The caches of the new renderer start empty, so glyphs and images are uploaded again as
+    /// the next frames draw them.
+    pub(crate) fn replace(&mut self, context: Context) {
+        let generation = self.generation + 1;
+        *self = context;
+        self.generation = generation;
+    }
+
+    /// Returns the settings the context was created with.
+    pub(crate) fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Returns the number of times the device was recreated.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Creates the swap chain of a surface, unless the geometry is empty.
+    pub(crate) fn create_swap_chain(
+        &self,
+        surface: &wgpu::Surface,
+        geometry: &Geometry,
+        format: wgpu::TextureFormat,
+        present_mode: wgpu::PresentMode,
+    ) -> Option<wgpu::SwapChain> {
+        if geometry.is_empty() {
+            return None;
+        }
+        let size = geometry.physical_size();
+
+        Some(self.device.create_swap_chain(
+            surface,
+            &wgpu::SwapChainDescriptor {
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+                format,
+                width: size.width,
+                height: size.height,
+                present_mode,
+            },
+        ))
+    }
+
+    /// Renders the primitive over the background color, and submits the frame.
+    pub(crate) fn draw<T: AsRef<str>>(
+        &mut self,
+        frame: &wgpu::SwapChainOutput,
+        viewport: &Viewport,
+        background_color: Color,
+        primitive: &(Primitive, mouse::Interaction),
+        overlay: &[T],
+    ) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: &frame.view,
+                resolve_target: None,
+                load_op: wgpu::LoadOp::Clear,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color {
+                    r: background_color.r as f64,
+                    g: background_color.g as f64,
+                    b: background_color.b as f64,
+                    a: background_color.a as f64,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        self.renderer.backend_mut().draw(
+            &mut self.device,
+            &mut encoder,
+            &frame.view,
+            viewport,
+            primitive,
+            overlay,
+        );
+
+        self.queue.submit(&[encoder.finish()]);
+    }
+}
+
+/// The contexts a view renders with, created on a background thread.
+#[cfg(target_os = "macos")]
+pub(crate) struct Contexts {
+    /// The context of the main thread, unless the view shares one which already exists.
+    pub(crate) main: Option<Context>,
+    /// The surface and the context of the render thread, if the view has one.
+    pub(crate) render: Option<(wgpu::Surface, Context)>,
+}
+
+/// Creates the contexts of a view, with adapters chosen for its layer.
+#[cfg(target_os = "macos")]
+pub(crate) struct Provider {
+    // The `CAMetalLayer` of the view, retained.
+    layer: usize,
+    settings: Settings,
+    is_main_shared: bool,
+}
+
+#[cfg(target_os = "macos")]
+impl Provider {
+    pub(crate) fn new(layer: *mut Object, settings: Settings, is_main_shared: bool) -> Self {
+        // The view may be deallocated before the contexts are created.
+        unsafe {
+            let _: *mut Object = msg_send![layer, retain];
+        }
+
+        Self {
+            layer: layer as usize,
+            settings,
+            is_main_shared,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl init::Provider for Provider {
+    type Device = Contexts;
+
+    fn create(self) -> Result<Contexts, Error> {
+        let layer = self.layer as *mut c_void;
+        let main = if self.is_main_shared {
+            None
+        } else {
+            // The main thread creates its own surface, this one only chooses the adapter.
+            let surface = wgpu::Surface::create_surface_from_core_animation_layer(layer);
+            Some(Context::new(&surface, self.settings.clone())?)
+        };
+        let render = if self.settings.render_thread {
+            let surface = wgpu::Surface::create_surface_from_core_animation_layer(layer);
+            let context = Context::new(&surface, self.settings.clone())?;
+            Some((surface, context))
+        } else {
+            None
+        };
+
+        Ok(Contexts { main, render })
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for Provider {
+    fn drop(&mut self) {
+        unsafe {
+            let () = msg_send![self.layer as *mut Object, release];
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn init_device_and_queue(
+    surface: &wgpu::Surface,
+    power_preference: gpu::PowerPreference,
+    backend: gpu::Backend,
+) -> Result<(wgpu::Device, wgpu::Queue), Error> {
+    let adapter = crate::futures::executor::block_on(wgpu::Adapter::request(
+        &wgpu::RequestAdapterOptions {
+            power_preference: power_preference.into(),
+            compatible_surface: Some(&surface),
+        },
+        backend.into(),
+    ))
+    .ok_or_else(|| backend.not_found())?;
+
+    // wgpu panics instead of reporting a failed device request.
+    crate::poison::catch(|| {
+        crate::futures::executor::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            extensions: wgpu::Extensions {
+                anisotropic_filtering: false,
+            },
+            limits: wgpu::Limits::default(),
+        }))
+    })
+    .map_err(|_| Error::DeviceRequestFailed)
+}
+
+/// Checks that the surface supports the format and the present mode of the settings.
+///
+/// wgpu panics when a swap chain is created with a configuration the surface doesn't support,
+/// so a tiny one is created and dropped right away, before the view relies on one.
+#[cfg(target_os = "macos")]
+fn check_surface(
+    device: &wgpu::Device,
+    surface: &wgpu::Surface,
+    settings: &Settings,
+) -> Result<(), Error> {
+    crate::poison::catch(|| {
+        device.create_swap_chain(
+            surface,
+            &wgpu::SwapChainDescriptor {
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+                format: settings.format.into(),
+                width: 1,
+                height: 1,
+                present_mode: settings.present_mode.into(),
+            },
+        )
+    })
+    .map(drop)
+    .map_err(|_| Error::UnsupportedSurface(settings.format, settings.present_mode))
+}
+
+/// The settings which must be equal for views to share a context.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug, Clone, PartialEq)]
+struct Key {
+    // Fonts are compared by content, as the same font may be loaded at different addresses.
+    default_font: Option<&'static [u8]>,
+    default_text_size: u16,
+    sample_count: u32,
+    power_preference: gpu::PowerPreference,
+    backend: gpu::Backend,
+    format: gpu::Format,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl Key {
+    fn new(settings: &Settings) -> Self {
+        Self {
+            default_font: settings.default_font,
+            default_text_size: settings.default_text_size,
+            sample_count: settings.samples(),
+            power_preference: settings.power_preference,
+            backend: settings.backend,
+            format: settings.format,
+        }
+    }
+}
+
+/// Values shared by key, which live as long as someone holds them.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug)]
+pub(crate) struct Registry<K, T> {
+    entries: Vec<(K, Weak<T>)>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl<K: PartialEq, T> Registry<K, T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the value of the key, if someone still holds it.
+    pub(crate) fn get(&mut self, key: &K) -> Option<Rc<T>> {
+        self.entries.retain(|(_, value)| value.strong_count() > 0);
+
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == key)
+            .and_then(|(_, value)| value.upgrade())
+    }
+
+    /// Returns the value of the key, creating it with `f` if nobody holds it anymore.
+    pub(crate) fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> T) -> Rc<T> {
+        if let Some(value) = self.get(&key) {
+            return value;
+        }
+
+        let value = Rc::new(f());
+        self.entries.push((key, Rc::downgrade(&value)));
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_values_by_key() {
+        let mut registry = Registry::new();
+
+        let first = registry.get_or_insert_with(1, || String::from("first"));
+        let shared = registry.get_or_insert_with(1, || unreachable!());
+        let other = registry.get_or_insert_with(2, || String::from("other"));
+
+        assert!(Rc::ptr_eq(&first, &shared));
+        assert!(Rc::ptr_eq(&first, &registry.get(&1).unwrap()));
+        assert_eq!(*other, "other");
+    }
+
+    #[test]
+    fn tears_down_with_last_holder() {
+        let mut registry = Registry::new();
+        let first = registry.get_or_insert_with(1, || String::from("first"));
+        let shared = registry.get(&1).unwrap();
+
+        drop(first);
+        assert!(registry.get(&1).is_some());
+
+        drop(shared);
+        assert!(registry.get(&1).is_none());
+        assert!(registry.entries.is_empty());
+
+        let second = registry.get_or_insert_with(1, || String::from("second"));
+        assert_eq!(*second, "second");
+    }
+
+    #[test]
+    fn compares_fonts_by_content() {
+        let font = vec![0u8, 1, 2, 3];
+        let copy = font.clone();
+        let settings = |font: &'static [u8]| Settings {
+            default_font: Some(font),
+            ..Settings::default()
+        };
+
+        let font: &'static [u8] = Box::leak(font.into_boxed_slice());
+        let copy: &'static [u8] = Box::leak(copy.into_boxed_slice());
+
+        assert_eq!(Key::new(&settings(font)), Key::new(&settings(copy)));
+        assert_ne!(Key::new(&settings(font)), Key::new(&settings(&font[1..])));
+        assert_ne!(Key::new(&settings(font)), Key::new(&Settings::default()));
+    }
+}
@@ -0,0 +1,507 @@
+//! Drag content out of the view to other applications.
+//!
+//! A drag is started from `Application::update` with the command returned by [`start`], while
+//! the left mouse button is held down. The view becomes the `NSDraggingSource` of the session
+//! and reports its [`Outcome`] back as a message once the user drops the content or cancels.
+//!
+//! [`start`]: fn.start.html
+//! [`Outcome`]: enum.Outcome.html
+
+use std::ops::BitOr;
+#[cfg(target_os = "macos")]
+use std::path::Path;
+use std::path::PathBuf;
+
+#[cfg(target_os = "macos")]
+use cocoa::base::{id, nil, BOOL, NO};
+#[cfg(target_os = "macos")]
+use cocoa::foundation::{NSArray, NSAutoreleasePool, NSPoint, NSRect, NSSize};
+
+#[cfg(target_os = "macos")]
+use objc::runtime::Object;
+#[cfg(target_os = "macos")]
+use objc::{class, msg_send, sel, sel_impl};
+
+use crate::clipboard::Content;
+use crate::command::{self, Command};
+#[cfg(any(target_os = "macos", test))]
+use crate::keyboard;
+#[cfg(target_os = "macos")]
+use crate::{clipboard::Kind, nsdata, nsstring};
+
+/// The distance the cursor travels with the button held down before a drag starts, in the
+/// coordinate system of the widgets.
+pub(crate) const THRESHOLD: f32 = 4.0;
+
+/// A set of drag operations, mirroring `NSDragOperation`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Operation(u64);
+
+impl Operation {
+    /// No operation, the drop is refused.
+    pub const NONE: Operation = Operation(0);
+    /// The data is copied.
+    pub const COPY: Operation = Operation(1);
+    /// The data is shared.
+    pub const LINK: Operation = Operation(2);
+    /// The operation is defined by the destination.
+    pub const GENERIC: Operation = Operation(4);
+    /// The operation is negotiated privately between the source and the destination.
+    pub const PRIVATE: Operation = Operation(8);
+    /// The data is moved.
+    pub const MOVE: Operation = Operation(16);
+    /// The data is deleted.
+    pub const DELETE: Operation = Operation(32);
+    /// Any operation.
+    pub const EVERY: Operation = Operation(u64::MAX);
+
+    /// Creates an operation set from the raw `NSDragOperation` bits.
+    pub fn from_bits(bits: u64) -> Self {
+        Operation(bits)
+    }
+
+    /// Returns the raw `NSDragOperation` bits.
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Returns `true` if no operation is set.
+    pub fn is_none(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all the operations of `other` are set.
+    pub fn contains(self, other: Operation) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the operations set in both `self` and `other`.
+    pub fn intersection(self, other: Operation) -> Operation {
+        Operation(self.0 & other.0)
+    }
+}
+
+impl BitOr for Operation {
+    type Output = Operation;
+
+    fn bitor(self, other: Operation) -> Operation {
+        Operation(self.0 | other.0)
+    }
+}
+
+/// Content dragged out of the view.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drag {
+    /// The representations of the dragged content.
+    ///
+    /// Every path of `Content::FileUrls` becomes its own dragging item, so Finder and hosts see
+    /// one file per item. The other representations are attached to a single item.
+    pub contents: Vec<Content>,
+    /// The encoded bytes of a PNG image shown under the cursor.
+    ///
+    /// If `None` is provided, file items use their Finder icon and the rest are dragged without
+    /// an image.
+    pub image: Option<Vec<u8>>,
+    /// The operations the destination may perform.
+    ///
+    /// By default, it's `Operation::COPY`.
+    pub operations: Operation,
+}
+
+impl Drag {
+    /// Creates a copy drag of the given content.
+    pub fn new(contents: Vec<Content>) -> Self {
+        Self {
+            contents,
+            image: None,
+            operations: Operation::COPY,
+        }
+    }
+
+    /// Creates a copy drag of the files at the given paths.
+    pub fn files(paths: Vec<PathBuf>) -> Self {
+        Self::new(vec![Content::FileUrls(paths)])
+    }
+
+    /// Sets the PNG image shown under the cursor.
+    pub fn image(mut self, png: Vec<u8>) -> Self {
+        self.image = Some(png);
+        self
+    }
+
+    /// Sets the operations the destination may perform.
+    pub fn operations(mut self, operations: Operation) -> Self {
+        self.operations = operations;
+        self
+    }
+}
+
+/// The way a drag session finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The content was dropped and the destination performed the operation.
+    Completed(Operation),
+    /// The user released the content over a destination which refused it, or pressed Escape.
+    Cancelled,
+    /// The session couldn't start, because another one is in progress or no mouse button is
+    /// held down.
+    Failed,
+}
+
+/// Creates a command which starts dragging the content out of the view.
+///
+/// It should be returned while handling a mouse press or drag, e.g. when a widget reports that
+/// the cursor has left it with the button held down. `f` receives the outcome of the session.
+pub fn start<T>(drag: Drag, f: impl Fn(Outcome) -> T + 'static + Send) -> Command<T> {
+    command::Action::Drag(Action {
+        drag,
+        on_end: Box::new(f),
+    })
+    .into()
+}
+
+/// A request to start a drag session.
+pub(crate) struct Action<T> {
+    drag: Drag,
+    on_end: Box<dyn Fn(Outcome) -> T + Send>,
+}
+
+impl<T> Action<T> {
+    pub(crate) fn map<A>(self, f: std::sync::Arc<dyn Fn(T) -> A + Send + Sync>) -> Action<A>
+    where
+        T: 'static,
+        A: 'static,
+    {
+        let on_end = self.on_end;
+
+        Action {
+            drag: self.drag,
+            on_end: Box::new(move |outcome| f(on_end(outcome))),
+        }
+    }
+}
+
+/// The source side of drag sessions started by the view.
+///
+/// At most one session is in progress at a time.
+#[cfg(any(target_os = "macos", test))]
+pub(crate) struct Source<T> {
+    session: Option<Session<T>>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+struct Session<T> {
+    operations: Operation,
+    on_end: Box<dyn Fn(Outcome) -> T + Send>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl<T> Source<T> {
+    pub(crate) fn new() -> Self {
+        Self { session: None }
+    }
+
+    /// Begins a session for the action.
+    ///
+    /// Returns the drag to hand over to AppKit or, if a session is already in progress, the
+    /// message reporting the failure.
+    pub(crate) fn begin(&mut self, action: Action<T>) -> Result<Drag, T> {
+        if self.session.is_some() {
+            return Err((action.on_end)(Outcome::Failed));
+        }
+
+        self.session = Some(Session {
+            operations: action.drag.operations,
+            on_end: action.on_end,
+        });
+
+        Ok(action.drag)
+    }
+
+    /// Returns the operations allowed by the session in progress.
+    pub(crate) fn operations(&self) -> Operation {
+        self.session
+            .as_ref()
+            .map(|session| session.operations)
+            .unwrap_or(Operation::NONE)
+    }
+
+    /// Ends the session in progress with the operation performed by the destination.
+    pub(crate) fn end(&mut self, operation: Operation) -> Option<T> {
+        let outcome = if operation.is_none() {
+            Outcome::Cancelled
+        } else {
+            Outcome::Completed(operation)
+        };
+
+        self.finish(outcome)
+    }
+
+    /// Ends the session in progress because AppKit couldn't start it.
+    pub(crate) fn fail(&mut self) -> Option<T> {
+        self.finish(Outcome::Failed)
+    }
+
+    fn finish(&mut self, outcome: Outcome) -> Option<T> {
+        self.session.take().map(|session| (session.on_end)(outcome))
+    }
+}
+
+/// Narrows the operations allowed by a session down to the ones the modifiers held down ask
+/// for, like Finder does: ⌥ copies, ⌘ moves and ⌥⌘ links.
+///
+/// Without these modifiers, or if the session doesn't allow what they ask for, the allowed
+/// operations are kept.
+#[cfg(any(target_os = "macos", test))]
+pub(crate) fn modified(allowed: Operation, modifiers: keyboard::ModifiersState) -> Operation {
+    let requested = match (modifiers.alt, modifiers.logo) {
+        (true, true) => Operation::LINK,
+        (true, false) => Operation::COPY,
+        (false, true) => Operation::MOVE,
+        (false, false) => return allowed,
+    };
+
+    match allowed.intersection(requested) {
+        narrowed if narrowed.is_none() => allowed,
+        narrowed => narrowed,
+    }
+}
+
+/// Starts an AppKit dragging session with `view` as its source.
+///
+/// `event` is the mouse event which initiated the drag. Returns `false` if the session couldn't
+/// be started.
+#[cfg(target_os = "macos")]
+pub(crate) unsafe fn begin_session(view: *mut Object, event: id, drag: &Drag) -> bool {
+    if event.is_null() {
+        return false;
+    }
+
+    let location: NSPoint = msg_send![event, locationInWindow];
+    let location: NSPoint = msg_send![view, convertPoint: location fromView: nil];
+    let image = drag
+        .image
+        .as_ref()
+        .map(|png| image_from_png(png))
+        .unwrap_or(nil);
+
+    let mut writers = drag
+        .contents
+        .iter()
+        .filter_map(|content| match content {
+            Content::FileUrls(paths) => Some(paths),
+            _ => None,
+        })
+        .flatten()
+        .map(|path| {
+            let path_string = nsstring(&path.to_string_lossy());
+            let url: id = msg_send![class!(NSURL), fileURLWithPath: path_string];
+            let icon = if image.is_null() {
+                file_icon(path)
+            } else {
+                image
+            };
+            (url, icon)
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(item) = pasteboard_item(&drag.contents) {
+        writers.push((item, image));
+    }
+
+    if writers.is_empty() {
+        return false;
+    }
+
+    let items = writers
+        .into_iter()
+        .enumerate()
+        .map(|(n, (writer, image))| {
+            let allocation: id = msg_send![class!(NSDraggingItem), alloc];
+            let item: id = msg_send![allocation, initWithPasteboardWriter: writer];
+            let size: NSSize = if image.is_null() {
+                NSSize::new(1.0, 1.0)
+            } else {
+                msg_send![image, size]
+            };
+            // Stack the items slightly so every one of them stays visible.
+            let offset = n as f64 * 4.0;
+            let frame = NSRect::new(
+                NSPoint::new(
+                    location.x - size.width / 2.0 + offset,
+                    location.y - size.height / 2.0 + offset,
+                ),
+                size,
+            );
+            let () = msg_send![item, setDraggingFrame: frame contents: image];
+            item.autorelease()
+        })
+        .collect::<Vec<_>>();
+
+    let items = NSArray::arrayWithObjects(nil, &items);
+    let session: id = msg_send![
+        view,
+        beginDraggingSessionWithItems: items
+        event: event
+        source: view
+    ];
+
+    !session.is_null()
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn pasteboard_item(contents: &[Content]) -> Option<id> {
+    let contents = contents
+        .iter()
+        .filter(|content| content.kind() != Kind::FileUrls)
+        .collect::<Vec<_>>();
+
+    if contents.is_empty() {
+        return None;
+    }
+
+    let item: id = msg_send![class!(NSPasteboardItem), new];
+    contents.into_iter().for_each(|content| {
+        let kind = nsstring(content.kind().uti());
+        let _: BOOL = match content {
+            Content::Text(string) | Content::Rtf(string) | Content::Html(string) => {
+                msg_send![item, setString: nsstring(string) forType: kind]
+            }
+            Content::Png(data) | Content::Custom { data, .. } => {
+                msg_send![item, setData: nsdata(data) forType: kind]
+            }
+            Content::FileUrls(_) => NO,
+        };
+    });
+
+    Some(item.autorelease())
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn image_from_png(png: &[u8]) -> id {
+    let allocation: id = msg_send![class!(NSImage), alloc];
+    let image: id = msg_send![allocation, initWithData: nsdata(png)];
+
+    if image.is_null() {
+        nil
+    } else {
+        image.autorelease()
+    }
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn file_icon(path: &Path) -> id {
+    let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+    msg_send![workspace, iconForFile: nsstring(&path.to_string_lossy())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    fn action(drag: Drag) -> Action<Outcome> {
+        Action {
+            drag,
+            on_end: Box::new(|outcome: Outcome| outcome),
+        }
+    }
+
+    fn preset() -> Drag {
+        Drag::new(vec![Content::Text(String::from("Pad"))])
+            .operations(Operation::COPY | Operation::MOVE)
+    }
+
+    #[test]
+    fn is_idle_without_a_session() {
+        let mut source = Source::<Outcome>::new();
+
+        assert_eq!(source.operations(), Operation::NONE);
+        assert_eq!(source.end(Operation::COPY), None);
+        assert_eq!(source.fail(), None);
+    }
+
+    #[test]
+    fn begins_one_session_at_a_time() {
+        let mut source = Source::new();
+
+        assert_eq!(source.begin(action(preset())), Ok(preset()));
+        assert_eq!(source.operations(), Operation::COPY | Operation::MOVE);
+
+        let other = Drag::files(vec![PathBuf::from("/tmp/clip.wav")]);
+        assert_eq!(source.begin(action(other)), Err(Outcome::Failed));
+        assert_eq!(source.operations(), Operation::COPY | Operation::MOVE);
+    }
+
+    #[test]
+    fn completes_with_the_performed_operation() {
+        let mut source = Source::new();
+        let _ = source.begin(action(preset()));
+
+        assert_eq!(
+            source.end(Operation::MOVE),
+            Some(Outcome::Completed(Operation::MOVE))
+        );
+        assert_eq!(source.operations(), Operation::NONE);
+        assert_eq!(source.end(Operation::MOVE), None);
+    }
+
+    #[test]
+    fn cancels_when_nothing_was_performed() {
+        let mut source = Source::new();
+        let _ = source.begin(action(preset()));
+
+        assert_eq!(source.end(Operation::NONE), Some(Outcome::Cancelled));
+    }
+
+    #[test]
+    fn fails_when_the_session_does_not_start() {
+        let mut source = Source::new();
+        let _ = source.begin(action(preset()));
+
+        assert_eq!(source.fail(), Some(Outcome::Failed));
+        assert_eq!(source.begin(action(preset())), Ok(preset()));
+    }
+
+    fn modifiers(alt: bool, logo: bool) -> keyboard::ModifiersState {
+        keyboard::ModifiersState {
+            shift: false,
+            control: false,
+            alt,
+            logo,
+        }
+    }
+
+    #[test]
+    fn modifiers_narrow_the_allowed_operations() {
+        let allowed = Operation::COPY | Operation::MOVE | Operation::LINK;
+
+        assert_eq!(modified(allowed, modifiers(false, false)), allowed);
+        assert_eq!(modified(allowed, modifiers(true, false)), Operation::COPY);
+        assert_eq!(modified(allowed, modifiers(false, true)), Operation::MOVE);
+        assert_eq!(modified(allowed, modifiers(true, true)), Operation::LINK);
+    }
+
+    #[test]
+    fn modifiers_keep_the_allowed_operations_they_do_not_match() {
+        assert_eq!(
+            modified(Operation::COPY, modifiers(false, true)),
+            Operation::COPY
+        );
+        assert_eq!(
+            modified(Operation::NONE, modifiers(true, false)),
+            Operation::NONE
+        );
+    }
+
+    #[test]
+    fn maps_the_outcome() {
+        let mut source = Source::new();
+        let action = action(preset())
+            .map::<bool>(Arc::new(|outcome: Outcome| outcome == Outcome::Cancelled));
+        let _ = source.begin(action);
+
+        assert_eq!(source.end(Operation::NONE), Some(true));
+    }
+}
@@ -0,0 +1,345 @@
+//! Accept content dragged into the view.
+//!
+//! The view registers for the kinds listed in `Settings::drop_types`. While a drag hovers over
+//! it, `Application::drop_operation` decides which [`Operation`] the view accepts at the cursor
+//! position, and `Application::on_drop` turns the resulting [`Event`]s into messages.
+//!
+//! [`Operation`]: ../drag/struct.Operation.html
+//! [`Event`]: enum.Event.html
+
+use std::path::PathBuf;
+
+#[cfg(any(target_os = "macos", test))]
+use crate::clipboard::Clipboard;
+use crate::clipboard::{Content, Kind};
+use crate::drag::Operation;
+use crate::Point;
+
+/// Content dragged over the view.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payload {
+    contents: Vec<Content>,
+    operations: Operation,
+}
+
+impl Payload {
+    /// Creates a payload with the representations of the dragged content and the operations
+    /// allowed by the drag source.
+    pub fn new(contents: Vec<Content>, operations: Operation) -> Self {
+        Self {
+            contents,
+            operations,
+        }
+    }
+
+    /// Returns all the representations of the dragged content.
+    pub fn contents(&self) -> &[Content] {
+        &self.contents
+    }
+
+    /// Returns the representation of the given kind, if the source provided one.
+    pub fn get(&self, kind: &Kind) -> Option<&Content> {
+        self.contents.iter().find(|content| &content.kind() == kind)
+    }
+
+    /// Returns the dragged text, if any.
+    pub fn text(&self) -> Option<&str> {
+        match self.get(&Kind::Text) {
+            Some(Content::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the paths of the dragged files.
+    pub fn files(&self) -> &[PathBuf] {
+        match self.get(&Kind::FileUrls) {
+            Some(Content::FileUrls(paths)) => paths,
+            _ => &[],
+        }
+    }
+
+    /// Returns the operations allowed by the drag source.
+    pub fn source_operations(&self) -> Operation {
+        self.operations
+    }
+
+    /// Reads every representation of the given kinds available on the clipboard.
+    #[cfg(any(target_os = "macos", test))]
+    pub(crate) fn read(clipboard: &dyn Clipboard, kinds: &[Kind], operations: Operation) -> Self {
+        let contents = kinds
+            .iter()
+            .filter_map(|kind| clipboard.read(kind))
+            .collect();
+
+        Self::new(contents, operations)
+    }
+}
+
+/// An event produced while content is dragged over the view.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A drag entered the view.
+    Entered(Payload),
+    /// The cursor moved over the view, or the accepted operation changed.
+    Hovered {
+        /// The position of the cursor in the view.
+        position: Point,
+        /// The operation the view accepts at this position.
+        operation: Operation,
+    },
+    /// The drag left the view without dropping.
+    Left,
+    /// The content was dropped on the view.
+    Dropped {
+        /// The dropped content.
+        payload: Payload,
+        /// The position of the drop in the view.
+        position: Point,
+        /// The accepted operation.
+        operation: Operation,
+    },
+}
+
+/// The destination side of drag sessions entering the view.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug, Default)]
+pub(crate) struct Destination {
+    payload: Option<Payload>,
+    position: Option<Point>,
+    operation: Operation,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl Destination {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking a drag which entered the view.
+    pub(crate) fn enter(&mut self, payload: Payload) -> Event {
+        self.payload = Some(payload.clone());
+        self.position = None;
+        self.operation = Operation::NONE;
+
+        Event::Entered(payload)
+    }
+
+    /// Decides the operation at the new cursor position.
+    ///
+    /// `decide` may return several operations, the one to perform is chosen among those allowed
+    /// by the source. Returns the accepted operation and, if the position or the operation
+    /// changed, the event reporting it.
+    pub(crate) fn update(
+        &mut self,
+        position: Point,
+        decide: impl FnOnce(&Payload, Point) -> Operation,
+    ) -> (Operation, Option<Event>) {
+        let payload = match &self.payload {
+            Some(payload) => payload,
+            None => return (Operation::NONE, None),
+        };
+        let operation = resolve(decide(payload, position), payload.source_operations());
+
+        if self.position == Some(position) && self.operation == operation {
+            return (operation, None);
+        }
+
+        self.position = Some(position);
+        self.operation = operation;
+
+        (
+            operation,
+            Some(Event::Hovered {
+                position,
+                operation,
+            }),
+        )
+    }
+
+    /// Stops tracking the drag because it left the view.
+    pub(crate) fn leave(&mut self) -> Option<Event> {
+        self.payload.take().map(|_| {
+            self.position = None;
+            self.operation = Operation::NONE;
+            Event::Left
+        })
+    }
+
+    /// Drops the tracked drag, if the last decided operation accepts it.
+    pub(crate) fn perform(&mut self) -> Option<Event> {
+        if self.operation.is_none() {
+            return None;
+        }
+
+        match (self.payload.take(), self.position.take()) {
+            (Some(payload), Some(position)) => Some(Event::Dropped {
+                payload,
+                position,
+                operation: std::mem::take(&mut self.operation),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Forgets the tracked drag once the session is over.
+    #[cfg(target_os = "macos")]
+    pub(crate) fn end(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Chooses the single operation to perform among the requested ones allowed by the source.
+///
+/// Copy is preferred, then move, link, generic, private and delete, in this order.
+#[cfg(any(target_os = "macos", test))]
+pub(crate) fn resolve(requested: Operation, allowed: Operation) -> Operation {
+    let candidates = requested.intersection(allowed);
+
+    [
+        Operation::COPY,
+        Operation::MOVE,
+        Operation::LINK,
+        Operation::GENERIC,
+        Operation::PRIVATE,
+        Operation::DELETE,
+    ]
+    .iter()
+    .copied()
+    .find(|operation| candidates.contains(*operation))
+    .unwrap_or(Operation::NONE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::clipboard::Memory;
+
+    fn wav() -> Payload {
+        Payload::new(
+            vec![Content::FileUrls(vec![PathBuf::from("/tmp/kick.wav")])],
+            Operation::COPY | Operation::LINK,
+        )
+    }
+
+    #[test]
+    fn resolve_prefers_copy_then_move_then_link() {
+        let every = Operation::EVERY;
+
+        assert_eq!(resolve(every, every), Operation::COPY);
+        assert_eq!(
+            resolve(Operation::MOVE | Operation::LINK, every),
+            Operation::MOVE
+        );
+        assert_eq!(
+            resolve(Operation::LINK | Operation::GENERIC, every),
+            Operation::LINK
+        );
+        assert_eq!(
+            resolve(Operation::PRIVATE | Operation::DELETE, every),
+            Operation::PRIVATE
+        );
+    }
+
+    #[test]
+    fn resolve_only_performs_what_the_source_allows() {
+        assert_eq!(
+            resolve(Operation::COPY | Operation::MOVE, Operation::MOVE),
+            Operation::MOVE
+        );
+        assert_eq!(resolve(Operation::COPY, Operation::MOVE), Operation::NONE);
+        assert_eq!(resolve(Operation::NONE, Operation::EVERY), Operation::NONE);
+    }
+
+    #[test]
+    fn payload_reads_the_registered_kinds() {
+        let mut clipboard = Memory::new();
+        clipboard.write(vec![
+            Content::Text(String::from("kick.wav")),
+            Content::FileUrls(vec![PathBuf::from("/tmp/kick.wav")]),
+            Content::Png(vec![0x89]),
+        ]);
+
+        let payload = Payload::read(
+            &clipboard,
+            &[Kind::FileUrls, Kind::Text, Kind::Html],
+            Operation::COPY,
+        );
+
+        assert_eq!(payload.contents().len(), 2);
+        assert_eq!(payload.text(), Some("kick.wav"));
+        assert_eq!(payload.files(), &[PathBuf::from("/tmp/kick.wav")][..]);
+        assert_eq!(payload.get(&Kind::Png), None);
+        assert_eq!(payload.source_operations(), Operation::COPY);
+    }
+
+    #[test]
+    fn reports_hovers_when_the_position_or_operation_changes() {
+        let mut destination = Destination::new();
+        assert_eq!(destination.enter(wav()), Event::Entered(wav()));
+
+        let position = Point::new(10.0, 20.0);
+        let (operation, event) = destination.update(position, |_, _| Operation::EVERY);
+        assert_eq!(operation, Operation::COPY);
+        assert_eq!(
+            event,
+            Some(Event::Hovered {
+                position,
+                operation: Operation::COPY
+            })
+        );
+
+        let (_, event) = destination.update(position, |_, _| Operation::EVERY);
+        assert_eq!(event, None);
+
+        let (operation, event) = destination.update(position, |_, _| Operation::LINK);
+        assert_eq!(operation, Operation::LINK);
+        assert!(event.is_some());
+    }
+
+    #[test]
+    fn drops_with_the_last_accepted_operation() {
+        let mut destination = Destination::new();
+        let _ = destination.enter(wav());
+
+        let position = Point::new(10.0, 20.0);
+        let _ = destination.update(position, |payload, _| {
+            if payload.files().is_empty() {
+                Operation::NONE
+            } else {
+                Operation::LINK
+            }
+        });
+
+        assert_eq!(
+            destination.perform(),
+            Some(Event::Dropped {
+                payload: wav(),
+                position,
+                operation: Operation::LINK,
+            })
+        );
+        assert_eq!(destination.perform(), None);
+    }
+
+    #[test]
+    fn refused_drags_are_not_dropped() {
+        let mut destination = Destination::new();
+        let _ = destination.enter(wav());
+        let _ = destination.update(Point::new(1.0, 1.0), |_, _| Operation::MOVE);
+
+        assert_eq!(destination.perform(), None);
+        assert_eq!(destination.leave(), Some(Event::Left));
+        assert_eq!(destination.leave(), None);
+    }
+
+    #[test]
+    fn ignores_updates_without_a_drag() {
+        let mut destination = Destination::new();
+
+        let (operation, event) = destination.update(Point::ORIGIN, |_, _| Operation::COPY);
+
+        assert_eq!(operation, Operation::NONE);
+        assert_eq!(event, None);
+    }
+}
@@ -0,0 +1,76 @@
+//! Report why a view couldn't be created.
+
+use std::fmt;
+
+use crate::poison::Panic;
+use crate::{gpu, Size};
+
+/// An error which prevents the creation of a view.
+///
+/// Invalid arguments are returned by `IcedView::try_new`, and nothing is left behind. Errors
+/// creating the GPU device happen later, as it's created in the background: they're reported to
+/// the function set with `IcedView::set_init_handler`, and by `IcedView::init_error`. Either
+/// way, hosts can fall back to their own user interface, e.g. the generic editor of a plugin,
+/// and try again later.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// No GPU adapter compatible with the view is available.
+    AdapterNotFound,
+    /// No GPU adapter compatible with the view supports the backend of the settings.
+    UnsupportedBackend(gpu::Backend),
+    /// The surface of the view doesn't support the format or the present mode of the settings.
+    UnsupportedSurface(gpu::Format, gpu::PresentMode),
+    /// The adapter failed to provide a device.
+    DeviceRequestFailed,
+    /// The layer the view renders to couldn't be created.
+    SurfaceCreationFailed,
+    /// The `NSView` subclass couldn't be registered with the Objective-C runtime.
+    ClassRegistrationFailed,
+    /// The size of the view is negative or isn't finite.
+    InvalidSize(Size),
+    /// The MSAA sample count of the settings isn't 1, 2, 4, 8 or 16.
+    InvalidSampleCount(u32),
+    /// A thread of the view, creating its GPU device or rendering it, couldn't be started.
+    ThreadSpawnFailed,
+    /// The application panicked while the view started, once its GPU device was created.
+    Panicked(Panic),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::AdapterNotFound => write!(f, "no compatible GPU adapter was found"),
+            Error::UnsupportedBackend(backend) => {
+                write!(f, "no compatible GPU adapter supports {:?}", backend)
+            }
+            Error::UnsupportedSurface(format, present_mode) => write!(
+                f,
+                "the surface doesn't support the format {:?} with the present mode {:?}",
+                format, present_mode
+            ),
+            Error::DeviceRequestFailed => write!(f, "the GPU device request failed"),
+            Error::SurfaceCreationFailed => write!(f, "the rendering surface couldn't be created"),
+            Error::ClassRegistrationFailed => write!(f, "the view class couldn't be registered"),
+            Error::InvalidSize(size) => write!(
+                f,
+                "the size {}x{} is negative or isn't finite",
+                size.width, size.height
+            ),
+            Error::InvalidSampleCount(count) => write!(
+                f,
+                "the sample count {} isn't one of 1, 2, 4, 8 or 16",
+                count
+            ),
+            Error::ThreadSpawnFailed => write!(f, "a thread of the view couldn't be started"),
+            Error::Panicked(panic) => {
+                write!(
+                    f,
+                    "the application panicked while the view started: {}",
+                    panic
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,398 @@
+//! Track the size of the view in points along with its scale factor and zoom.
+//!
+//! AppKit measures views in points, while the swap chain is measured in pixels. A [`Geometry`]
+//! always stores the size in points and the scale factor of the backing store, and derives the
+//! physical size from them, so both sides stay consistent on every display.
+//!
+//! On top of that, the zoom scales the user interface independently of the display: with a
+//! zoom of `2.0`, widgets are laid out in half the points of the view and drawn twice as big.
+//! Alternatively, a reference size scales the user interface uniformly so a layout designed at
+//! this size fits the view.
+//!
+//! [`Geometry`]: struct.Geometry.html
+
+#[cfg(any(target_os = "macos", test))]
+use iced_wgpu::Viewport;
+
+use crate::{Point, Size};
+
+/// The size of the view in points, the scale factor of its backing store and the zoom of the
+/// user interface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geometry {
+    size: Size,
+    scale_factor: f64,
+    zoom: f64,
+    reference_size: Option<Size>,
+}
+
+impl Geometry {
+    /// Creates a geometry with the given size in points and scale factor.
+    ///
+    /// Negative or non-finite dimensions are treated as zero, and a scale factor which isn't
+    /// positive and finite (e.g. the `0.0` returned for a view without window) as `1.0`.
+    pub fn new(size: Size, scale_factor: f64) -> Self {
+        Self {
+            size: Size::new(
+                sanitize_dimension(size.width),
+                sanitize_dimension(size.height),
+            ),
+            scale_factor: sanitize_factor(scale_factor),
+            zoom: 1.0,
+            reference_size: None,
+        }
+    }
+
+    /// Returns the size in points.
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Returns the scale factor of the backing store.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Returns the zoom of the user interface.
+    pub fn zoom(&self) -> f64 {
+        self.zoom
+    }
+
+    /// Returns the size the user interface is designed at, if it's scaled to fit the view.
+    pub fn reference_size(&self) -> Option<Size> {
+        self.reference_size
+    }
+
+    /// Returns the factor from the points of the view to the coordinates of the widgets.
+    ///
+    /// It's the zoom, unless a reference size is set. Then, it's the largest factor which fits
+    /// the reference size in the view.
+    pub fn ui_scale(&self) -> f64 {
+        match self.reference_size {
+            Some(reference_size) => sanitize_factor(f64::min(
+                f64::from(self.size.width) / f64::from(reference_size.width),
+                f64::from(self.size.height) / f64::from(reference_size.height),
+            )),
+            None => self.zoom,
+        }
+    }
+
+    /// Returns the size the widgets are laid out in, which is the size in points divided by the
+    /// UI scale.
+    pub fn logical_size(&self) -> Size {
+        let ui_scale = self.ui_scale();
+
+        Size::new(
+            (f64::from(self.size.width) / ui_scale) as f32,
+            (f64::from(self.size.height) / ui_scale) as f32,
+        )
+    }
+
+    /// Returns the size in pixels.
+    pub fn physical_size(&self) -> Size<u32> {
+        Size::new(
+            to_physical(self.size.width, self.scale_factor),
+            to_physical(self.size.height, self.scale_factor),
+        )
+    }
+
+    /// Returns `true` if the view covers no pixel, so nothing can be rendered.
+    pub fn is_empty(&self) -> bool {
+        let physical_size = self.physical_size();
+
+        physical_size.width == 0 || physical_size.height == 0
+    }
+
+    /// Returns the same geometry with another size in points.
+    pub fn with_size(self, size: Size) -> Self {
+        Self {
+            size: Self::new(size, self.scale_factor).size,
+            ..self
+        }
+    }
+
+    /// Returns the same geometry with another scale factor.
+    pub fn with_scale_factor(self, scale_factor: f64) -> Self {
+        Self {
+            scale_factor: sanitize_factor(scale_factor),
+            ..self
+        }
+    }
+
+    /// Returns the same geometry with another zoom.
+    ///
+    /// A zoom which isn't positive and finite is treated as `1.0`.
+    pub fn with_zoom(self, zoom: f64) -> Self {
+        Self {
+            zoom: sanitize_factor(zoom),
+            ..self
+        }
+    }
+
+    /// Returns the same geometry with another reference size.
+    ///
+    /// With a reference size, the zoom is ignored. A reference size which covers no point is
+    /// treated as `None`.
+    pub fn with_reference_size(self, reference_size: Option<Size>) -> Self {
+        Self {
+            reference_size: sanitize_reference_size(reference_size),
+            ..self
+        }
+    }
+
+    /// Converts a point in the coordinate system of the view to the coordinate system of the
+    /// widgets.
+    pub fn to_logical(&self, point: Point) -> Point {
+        let ui_scale = self.ui_scale();
+
+        Point::new(
+            (f64::from(point.x) / ui_scale) as f32,
+            (f64::from(point.y) / ui_scale) as f32,
+        )
+    }
+
+    /// Converts a point in the coordinate system of the widgets to the coordinate system of the
+    /// view.
+    pub fn to_points(&self, point: Point) -> Point {
+        let ui_scale = self.ui_scale();
+
+        Point::new(
+            (f64::from(point.x) * ui_scale) as f32,
+            (f64::from(point.y) * ui_scale) as f32,
+        )
+    }
+
+    /// Returns the viewport to render the geometry with.
+    ///
+    /// The viewport covers every pixel of the view, and its scale factor includes the UI scale so
+    /// the widgets fill the logical size.
+    #[cfg(any(target_os = "macos", test))]
+    pub(crate) fn viewport(&self) -> Viewport {
+        Viewport::with_physical_size(self.physical_size(), self.scale_factor * self.ui_scale())
+    }
+}
+
+/// How the view reacts to a change of zoom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoomMode {
+    /// The view keeps its size in points, so the widgets get more or less room.
+    KeepSize,
+    /// The view is resized so the widgets keep the same logical size.
+    KeepLayout,
+}
+
+impl Default for ZoomMode {
+    fn default() -> Self {
+        ZoomMode::KeepSize
+    }
+}
+
+/// How a user interface with a reference size fills the space left around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fit {
+    /// The widgets are laid out at the reference size and centered, the remaining space is
+    /// filled with the background color.
+    Letterbox,
+    /// The widgets are laid out in the whole view, at the scale of the reference size.
+    Expand,
+}
+
+impl Default for Fit {
+    fn default() -> Self {
+        Fit::Letterbox
+    }
+}
+
+fn sanitize_dimension(value: f32) -> f32 {
+    if value.is_finite() && value > 0.0 {
+        value
+    } else {
+        0.0
+    }
+}
+
+/// Returns the reference size, unless it covers no point.
+pub(crate) fn sanitize_reference_size(reference_size: Option<Size>) -> Option<Size> {
+    reference_size.filter(|size| {
+        sanitize_dimension(size.width) > 0.0 && sanitize_dimension(size.height) > 0.0
+    })
+}
+
+fn sanitize_factor(value: f64) -> f64 {
+    if value.is_finite() && value > 0.0 {
+        value
+    } else {
+        1.0
+    }
+}
+
+fn to_physical(value: f32, scale_factor: f64) -> u32 {
+    (f64::from(value) * scale_factor).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_the_physical_size_from_points_and_scale() {
+        let geometry = Geometry::new(Size::new(400.0, 300.5), 2.0);
+
+        assert_eq!(geometry.size(), Size::new(400.0, 300.5));
+        assert_eq!(geometry.physical_size(), Size::new(800, 601));
+        assert_eq!(
+            geometry.with_scale_factor(1.5).physical_size(),
+            Size::new(600, 451)
+        );
+    }
+
+    #[test]
+    fn sanitizes_degenerate_sizes() {
+        let geometry = Geometry::new(Size::new(-10.0, f32::NAN), 2.0);
+        assert_eq!(geometry.size(), Size::new(0.0, 0.0));
+        assert!(geometry.is_empty());
+
+        let geometry = geometry.with_size(Size::new(f32::INFINITY, 100.0));
+        assert_eq!(geometry.size(), Size::new(0.0, 100.0));
+        assert!(geometry.is_empty());
+    }
+
+    #[test]
+    fn sanitizes_the_scale_factor_of_views_without_window() {
+        assert_eq!(
+            Geometry::new(Size::new(10.0, 10.0), 0.0).scale_factor(),
+            1.0
+        );
+        assert_eq!(
+            Geometry::new(Size::new(10.0, 10.0), f64::NAN).scale_factor(),
+            1.0
+        );
+        assert_eq!(
+            Geometry::new(Size::new(10.0, 10.0), 2.0)
+                .with_scale_factor(-1.0)
+                .scale_factor(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn is_empty_below_one_pixel() {
+        assert!(Geometry::new(Size::new(0.2, 100.0), 2.0).is_empty());
+        assert!(!Geometry::new(Size::new(0.3, 100.0), 2.0).is_empty());
+    }
+
+    #[test]
+    fn keeps_the_scale_when_resized() {
+        let geometry = Geometry::new(Size::new(100.0, 100.0), 2.0).with_size(Size::new(50.0, 20.0));
+
+        assert_eq!(geometry.scale_factor(), 2.0);
+        assert_eq!(geometry.physical_size(), Size::new(100, 40));
+    }
+
+    fn assert_close(a: Point, b: Point) {
+        assert!(
+            (a.x - b.x).abs() < 1e-3 && (a.y - b.y).abs() < 1e-3,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn zoom_scales_the_widgets_but_not_the_pixels() {
+        let geometry = Geometry::new(Size::new(400.0, 300.0), 2.0).with_zoom(1.5);
+
+        assert_eq!(geometry.ui_scale(), 1.5);
+        assert_eq!(
+            geometry.logical_size(),
+            Size::new((400.0f64 / 1.5) as f32, 200.0)
+        );
+        assert_eq!(geometry.physical_size(), Size::new(800, 600));
+
+        let viewport = geometry.viewport();
+        assert_eq!(viewport.physical_size(), Size::new(800, 600));
+        assert_eq!(viewport.scale_factor(), 3.0);
+    }
+
+    #[test]
+    fn reference_size_overrides_the_zoom() {
+        let geometry = Geometry::new(Size::new(400.0, 300.0), 2.0)
+            .with_zoom(1.5)
+            .with_reference_size(Some(Size::new(200.0, 200.0)));
+
+        assert_eq!(geometry.ui_scale(), 1.5);
+        assert_eq!(
+            geometry
+                .with_reference_size(Some(Size::new(100.0, 50.0)))
+                .ui_scale(),
+            4.0
+        );
+        assert_eq!(
+            geometry
+                .with_reference_size(Some(Size::new(800.0, 300.0)))
+                .logical_size(),
+            Size::new(800.0, 600.0)
+        );
+        assert_eq!(geometry.viewport().scale_factor(), 3.0);
+    }
+
+    #[test]
+    fn ignores_degenerate_zooms_and_reference_sizes() {
+        let geometry = Geometry::new(Size::new(400.0, 300.0), 2.0);
+
+        assert_eq!(geometry.with_zoom(0.0).ui_scale(), 1.0);
+        assert_eq!(geometry.with_zoom(f64::INFINITY).ui_scale(), 1.0);
+        assert_eq!(
+            geometry
+                .with_reference_size(Some(Size::new(0.0, 100.0)))
+                .reference_size(),
+            None
+        );
+    }
+
+    #[test]
+    fn converts_points_to_the_coordinates_of_the_widgets() {
+        let geometry = Geometry::new(Size::new(400.0, 300.0), 2.0);
+
+        assert_eq!(
+            geometry.with_zoom(2.0).to_logical(Point::new(100.0, 50.0)),
+            Point::new(50.0, 25.0)
+        );
+        assert_eq!(
+            geometry
+                .with_zoom(0.5)
+                .with_scale_factor(1.0)
+                .to_logical(Point::new(100.0, 50.0)),
+            Point::new(200.0, 100.0)
+        );
+        assert_eq!(
+            geometry
+                .with_reference_size(Some(Size::new(800.0, 600.0)))
+                .to_logical(Point::new(100.0, 50.0)),
+            Point::new(200.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn converts_back_and_forth() {
+        let geometries = [
+            Geometry::new(Size::new(400.0, 300.0), 1.0),
+            Geometry::new(Size::new(400.0, 300.0), 2.0).with_zoom(0.75),
+            Geometry::new(Size::new(400.0, 300.0), 3.0).with_zoom(1.25),
+            Geometry::new(Size::new(333.0, 250.0), 2.0)
+                .with_reference_size(Some(Size::new(640.0, 480.0))),
+        ];
+        let points = [
+            Point::ORIGIN,
+            Point::new(12.5, 7.25),
+            Point::new(399.0, 299.0),
+        ];
+
+        for geometry in geometries.iter() {
+            for point in points.iter() {
+                assert_close(geometry.to_points(geometry.to_logical(*point)), *point);
+                assert_close(geometry.to_logical(geometry.to_points(*point)), *point);
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Configure how the view uses the GPU.
+//!
+//! These options are part of `Settings`. They're validated when the view is created, before
+//! anything is allocated, so a wrong value is reported as an [`Error`] instead of a panic deep
+//! inside `wgpu`.
+//!
+//! [`Error`]: ../enum.Error.html
+
+use iced_wgpu::{settings::Antialiasing, wgpu};
+
+use crate::Error;
+
+/// Which GPU the view prefers, on machines having several.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerPreference {
+    /// Let the system decide.
+    Default,
+    /// Prefer the integrated GPU, e.g. to keep a plugin from waking up the discrete one.
+    LowPower,
+    /// Prefer the discrete GPU.
+    HighPerformance,
+}
+
+impl Default for PowerPreference {
+    fn default() -> Self {
+        PowerPreference::Default
+    }
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(preference: PowerPreference) -> Self {
+        match preference {
+            PowerPreference::Default => wgpu::PowerPreference::Default,
+            PowerPreference::LowPower => wgpu::PowerPreference::LowPower,
+            PowerPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+/// The graphics API the view renders with.
+///
+/// Only the APIs able to render to the `CAMetalLayer` of the view are available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// The best API available on the system.
+    Primary,
+    /// Metal.
+    Metal,
+    /// Vulkan, through MoltenVK.
+    Vulkan,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend::Primary
+    }
+}
+
+impl Backend {
+    /// Returns the error for a view finding no adapter of this backend.
+    #[cfg(any(target_os = "macos", test))]
+    pub(crate) fn not_found(self) -> Error {
+        match self {
+            Backend::Primary => Error::AdapterNotFound,
+            backend => Error::UnsupportedBackend(backend),
+        }
+    }
+}
+
+impl From<Backend> for wgpu::BackendBit {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::Primary => wgpu::BackendBit::PRIMARY,
+            Backend::Metal => wgpu::BackendBit::METAL,
+            Backend::Vulkan => wgpu::BackendBit::VULKAN,
+        }
+    }
+}
+
+/// When rendered frames are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PresentMode {
+    /// Frames are shown as soon as they're rendered, which may tear.
+    Immediate,
+    /// Frames are shown on the next vertical blank, and a newer frame replaces a waiting one.
+    Mailbox,
+    /// Frames are shown on the next vertical blank, in order.
+    Fifo,
+}
+
+impl Default for PresentMode {
+    fn default() -> Self {
+        PresentMode::Mailbox
+    }
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+        }
+    }
+}
+
+/// The pixel format of the surface of the view.
+///
+/// Only the formats a `CAMetalLayer` can display are available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// 8 bits per channel, in the sRGB color space.
+    Bgra8UnormSrgb,
+    /// 8 bits per channel, without gamma correction.
+    Bgra8Unorm,
+    /// 16 bit floats per channel, for wide color and EDR displays.
+    Rgba16Float,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Bgra8UnormSrgb
+    }
+}
+
+impl From<Format> for wgpu::TextureFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Bgra8UnormSrgb => wgpu::TextureFormat::Bgra8UnormSrgb,
+            Format::Bgra8Unorm => wgpu::TextureFormat::Bgra8Unorm,
+            Format::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+        }
+    }
+}
+
+/// Returns the antialiasing of the renderer for a number of samples per pixel.
+///
+/// A single sample disables antialiasing.
+pub(crate) fn antialiasing(sample_count: u32) -> Result<Option<Antialiasing>, Error> {
+    match sample_count {
+        1 => Ok(None),
+        2 => Ok(Some(Antialiasing::MSAAx2)),
+        4 => Ok(Some(Antialiasing::MSAAx4)),
+        8 => Ok(Some(Antialiasing::MSAAx8)),
+        16 => Ok(Some(Antialiasing::MSAAx16)),
+        _ => Err(Error::InvalidSampleCount(sample_count)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Settings;
+
+    #[test]
+    fn maps_sample_counts() {
+        assert!(matches!(antialiasing(1), Ok(None)));
+        assert!(matches!(antialiasing(4), Ok(Some(Antialiasing::MSAAx4))));
+        assert!(matches!(antialiasing(16), Ok(Some(Antialiasing::MSAAx16))));
+        assert!(matches!(antialiasing(3), Err(Error::InvalidSampleCount(3))));
+    }
+
+    #[test]
+    fn reports_missing_backend() {
+        assert_eq!(Backend::Primary.not_found(), Error::AdapterNotFound);
+        assert_eq!(
+            Backend::Vulkan.not_found(),
+            Error::UnsupportedBackend(Backend::Vulkan)
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn honors_deprecated_antialiasing() {
+        let settings = Settings {
+            antialiasing: true,
+            ..Settings::default()
+        };
+        assert_eq!(settings.samples(), 4);
+        assert_eq!(settings.validate(), Ok(()));
+
+        let settings = Settings {
+            sample_count: 8,
+            ..settings
+        };
+        assert_eq!(settings.samples(), 8);
+
+        assert_eq!(Settings::default().samples(), 1);
+    }
+}
@@ -0,0 +1,290 @@
+//! Decide which clicks the view handles and which fall through to the views underneath.
+//!
+//! By default, the view handles every click within its bounds. In [`Mode::Widgets`], it lays
+//! the widget tree out and only handles the clicks on an interactive widget, so an overlay with
+//! a transparent background lets the clicks on its empty parts reach the host's own views.
+//! `Application::hit_test` can override the decision for custom hit regions.
+//!
+//! AppKit hit tests the view on every mouse move and cursor update. The layout of the widget
+//! tree is kept between hit tests, and the answer for a point until the interface changes.
+//!
+//! [`Mode::Widgets`]: enum.Mode.html#variant.Widgets
+
+use iced_native::{mouse, Element, UserInterface};
+use iced_wgpu::Primitive;
+
+use crate::{Point, Size};
+
+/// How the view decides whether a click belongs to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Every click within the bounds of the view is handled by the view.
+    Bounds,
+    /// Only the clicks on an interactive widget are handled by the view.
+    ///
+    /// Buttons, sliders, text inputs and the other widgets which respond to the cursor count.
+    /// Texts, images, containers and the space between widgets let clicks through.
+    Widgets,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Mode::Bounds
+    }
+}
+
+/// The output of a renderer, which tells how the cursor interacts with what was drawn.
+pub trait Interactive {
+    /// Returns the interaction of the cursor.
+    fn interaction(&self) -> mouse::Interaction;
+}
+
+impl Interactive for (Primitive, mouse::Interaction) {
+    fn interaction(&self) -> mouse::Interaction {
+        self.1
+    }
+}
+
+/// Returns `true` if an interactive widget of the element lies under the point.
+///
+/// The element is laid out within `bounds`, then each widget under the point tells how the
+/// cursor would interact with it there.
+pub fn is_interactive<'a, Message, Renderer>(
+    element: Element<'a, Message, Renderer>,
+    bounds: Size,
+    renderer: &mut Renderer,
+    point: Point,
+) -> bool
+where
+    Renderer: iced_native::Renderer,
+    Renderer::Output: Interactive,
+{
+    Cache::new().is_interactive(|| element, bounds, renderer, point)
+}
+
+/// The layout of the last hit test, and its answer.
+#[derive(Default)]
+pub(crate) struct Cache {
+    layout: Option<iced_native::Cache>,
+    // The bounds and the point of the last hit test, and whether it hit, until the interface
+    // changes.
+    last: Option<(Size, Point, bool)>,
+}
+
+impl Cache {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the last answer, once the interface changed.
+    ///
+    /// The layout is kept: it's only laid out again if the widget tree changed.
+    #[cfg(any(target_os = "macos", test))]
+    pub(crate) fn invalidate(&mut self) {
+        self.last = None;
+    }
+
+    /// Returns `true` if an interactive widget of the element lies under the point, like
+    /// `is_interactive`.
+    ///
+    /// The element is only produced if the answer isn't known already.
+    pub(crate) fn is_interactive<'a, Message, Renderer>(
+        &mut self,
+        element: impl FnOnce() -> Element<'a, Message, Renderer>,
+        bounds: Size,
+        renderer: &mut Renderer,
+        point: Point,
+    ) -> bool
+    where
+        Renderer: iced_native::Renderer,
+        Renderer::Output: Interactive,
+    {
+        match self.last {
+            Some((last_bounds, last_point, hit))
+                if last_bounds == bounds && last_point == point =>
+            {
+                return hit
+            }
+            _ => (),
+        }
+
+        let layout = self.layout.take().unwrap_or_else(iced_native::Cache::new);
+        let user_interface = UserInterface::build(element(), bounds, layout, renderer);
+        let hit = user_interface.draw(renderer, point).interaction() != mouse::Interaction::Idle;
+        self.layout = Some(user_interface.into_cache());
+        self.last = Some((bounds, point, hit));
+
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use iced_native::{layout, Hasher, Layout, Length, Widget};
+
+    /// A renderer which only reports the interaction of the cursor.
+    struct Probe;
+
+    impl iced_native::Renderer for Probe {
+        type Output = mouse::Interaction;
+        type Defaults = ();
+    }
+
+    impl Interactive for mouse::Interaction {
+        fn interaction(&self) -> mouse::Interaction {
+            *self
+        }
+    }
+
+    /// A widget of a fixed size, with the given interaction under the cursor.
+    struct Knob {
+        size: Size,
+        interaction: mouse::Interaction,
+    }
+
+    impl Knob {
+        fn new(interaction: mouse::Interaction) -> Element<'static, (), Probe> {
+            Element::new(Self {
+                size: Size::new(100.0, 20.0),
+                interaction,
+            })
+        }
+    }
+
+    impl Widget<(), Probe> for Knob {
+        fn width(&self) -> Length {
+            Length::Shrink
+        }
+
+        fn height(&self) -> Length {
+            Length::Shrink
+        }
+
+        fn layout(&self, _renderer: &Probe, _limits: &layout::Limits) -> layout::Node {
+            layout::Node::new(self.size)
+        }
+
+        fn draw(
+            &self,
+            _renderer: &mut Probe,
+            _defaults: &(),
+            layout: Layout<'_>,
+            cursor_position: Point,
+        ) -> mouse::Interaction {
+            if layout.bounds().contains(cursor_position) {
+                self.interaction
+            } else {
+                mouse::Interaction::Idle
+            }
+        }
+
+        fn hash_layout(&self, _state: &mut Hasher) {}
+    }
+
+    /// Lays its children out from top to bottom, like a column.
+    struct Stack(Vec<Element<'static, (), Probe>>);
+
+    impl Widget<(), Probe> for Stack {
+        fn width(&self) -> Length {
+            Length::Shrink
+        }
+
+        fn height(&self) -> Length {
+            Length::Shrink
+        }
+
+        fn layout(&self, renderer: &Probe, limits: &layout::Limits) -> layout::Node {
+            let mut size = Size::ZERO;
+            let children = self
+                .0
+                .iter()
+                .map(|child| {
+                    let mut node = child.layout(renderer, limits);
+                    node.move_to(Point::new(0.0, size.height));
+                    size = Size::new(
+                        size.width.max(node.size().width),
+                        size.height + node.size().height,
+                    );
+                    node
+                })
+                .collect();
+
+            layout::Node::with_children(size, children)
+        }
+
+        fn draw(
+            &self,
+            renderer: &mut Probe,
+            defaults: &(),
+            layout: Layout<'_>,
+            cursor_position: Point,
+        ) -> mouse::Interaction {
+            self.0
+                .iter()
+                .zip(layout.children())
+                .map(|(child, layout)| child.draw(renderer, defaults, layout, cursor_position))
+                .find(|interaction| *interaction != mouse::Interaction::Idle)
+                .unwrap_or(mouse::Interaction::Idle)
+        }
+
+        fn hash_layout(&self, _state: &mut Hasher) {}
+    }
+
+    fn hud() -> Element<'static, (), Probe> {
+        Element::new(Stack(vec![
+            Knob::new(mouse::Interaction::Idle),
+            Knob::new(mouse::Interaction::Pointer),
+            Knob::new(mouse::Interaction::Text),
+        ]))
+    }
+
+    fn hits(point: Point) -> bool {
+        is_interactive(hud(), Size::new(400.0, 300.0), &mut Probe, point)
+    }
+
+    #[test]
+    fn hits_interactive_widgets() {
+        assert!(hits(Point::new(50.0, 30.0)));
+        assert!(hits(Point::new(50.0, 50.0)));
+    }
+
+    #[test]
+    fn lets_clicks_through_other_widgets() {
+        assert!(!hits(Point::new(50.0, 10.0)));
+    }
+
+    #[test]
+    fn lets_clicks_through_empty_space() {
+        assert!(!hits(Point::new(200.0, 30.0)));
+        assert!(!hits(Point::new(50.0, 200.0)));
+    }
+
+    #[test]
+    fn remembers_the_answer_until_the_interface_changes() {
+        let mut cache = Cache::new();
+        let mut views = 0;
+        let bounds = Size::new(400.0, 300.0);
+        let mut hits = |cache: &mut Cache, point| {
+            cache.is_interactive(
+                || {
+                    views += 1;
+                    hud()
+                },
+                bounds,
+                &mut Probe,
+                point,
+            )
+        };
+
+        assert!(hits(&mut cache, Point::new(50.0, 30.0)));
+        assert!(hits(&mut cache, Point::new(50.0, 30.0)));
+        assert!(!hits(&mut cache, Point::new(50.0, 10.0)));
+        cache.invalidate();
+        assert!(!hits(&mut cache, Point::new(50.0, 10.0)));
+
+        drop(hits);
+        assert_eq!(views, 3);
+    }
+}
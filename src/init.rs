@@ -0,0 +1,306 @@
+//! Create the GPU device of a view without blocking the main thread.
+//!
+//! Requesting an adapter and a device can take long enough for some hosts to flag the main
+//! thread as hung while a plugin editor opens. The view appears right away, filled with the
+//! background color of the application, while a [`Provider`] creates the device on a background
+//! thread. Events received meanwhile are queued, and handled in order once the device is ready,
+//! right before the first real frame.
+//!
+//! [`Provider`]: trait.Provider.html
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::thread;
+
+use iced_native::Event;
+
+#[cfg(target_os = "macos")]
+use crate::main_thread;
+use crate::Error;
+
+thread_local! {
+    // The callbacks waiting for their device, on the main thread.
+    static PENDING: RefCell<HashMap<u64, Callback>> = RefCell::new(HashMap::new());
+    static NEXT_ID: Cell<u64> = Cell::new(0);
+}
+
+type Callback = Box<dyn FnOnce(Box<dyn Any + Send>)>;
+
+/// Work run later on the thread which spawned a provider.
+pub(crate) type Work = Box<dyn FnOnce() + Send>;
+
+/// Creates a device, away from the main thread.
+pub(crate) trait Provider: Send + 'static {
+    type Device: Send + 'static;
+
+    fn create(self) -> Result<Self::Device, Error>;
+}
+
+/// Where the initialization of a view stands.
+#[derive(Debug)]
+pub(crate) enum Init<P> {
+    /// The device is being created. The view waits with what it needs to start, and the
+    /// events it received so far.
+    Pending { payload: P, events: Vec<Event> },
+    /// The view started.
+    Ready,
+    /// The device couldn't be created, or the view couldn't start with it.
+    Failed(Error),
+}
+
+impl<P> Init<P> {
+    pub(crate) fn new(payload: P) -> Self {
+        Init::Pending {
+            payload,
+            events: Vec::new(),
+        }
+    }
+
+    /// Returns what the view waits with, until it starts.
+    pub(crate) fn payload_mut(&mut self) -> Option<&mut P> {
+        match self {
+            Init::Pending { payload, .. } => Some(payload),
+            _ => None,
+        }
+    }
+
+    /// Keeps an event until the view starts. It's dropped if the view already started or
+    /// failed to.
+    pub(crate) fn queue_event(&mut self, event: Event) {
+        if let Init::Pending { events, .. } = self {
+            events.push(event);
+        }
+    }
+
+    /// Ends the initialization with the result of the provider.
+    ///
+    /// Returns what the view waited with, the result, and the events to handle in order if the
+    /// view starts. Returns `None` if the initialization already ended.
+    pub(crate) fn complete<D>(
+        &mut self,
+        result: Result<D, Error>,
+    ) -> Option<(P, Result<D, Error>, Vec<Event>)> {
+        if !self.is_pending() {
+            return None;
+        }
+
+        let next = match &result {
+            Ok(_) => Init::Ready,
+            Err(error) => Init::Failed(error.clone()),
+        };
+        match std::mem::replace(self, next) {
+            Init::Pending { payload, events } => Some((payload, result, events)),
+            _ => None,
+        }
+    }
+
+    /// Records why the view couldn't start with the device it was given.
+    pub(crate) fn fail(&mut self, error: Error) {
+        *self = Init::Failed(error);
+    }
+
+    /// Returns why the device couldn't be created, or the view couldn't start, if it couldn't.
+    pub(crate) fn error(&self) -> Option<&Error> {
+        match self {
+            Init::Failed(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn is_pending(&self) -> bool {
+        match self {
+            Init::Pending { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// A device being created, whose callback is cancelled when it's dropped.
+#[derive(Debug)]
+pub(crate) struct Request {
+    id: u64,
+}
+
+impl Drop for Request {
+    fn drop(&mut self) {
+        let callback = PENDING.with(|pending| pending.borrow_mut().remove(&self.id));
+        // Dropped outside of the borrow, as it may own anything.
+        drop(callback);
+    }
+}
+
+/// Creates the device on a background thread, then calls `done` with the result on the current
+/// thread, unless the request was dropped in the meantime.
+///
+/// `dispatch` is called from any thread, and runs its work later on the current thread, like
+/// [`main_queue`] does on the main thread. `done` is never called before `spawn` returns.
+///
+/// [`main_queue`]: fn.main_queue.html
+pub(crate) fn spawn<P: Provider>(
+    provider: P,
+    dispatch: impl Fn(Work) + Clone + Send + 'static,
+    done: impl FnOnce(Result<P::Device, Error>) + 'static,
+) -> Request {
+    let id = NEXT_ID.with(|next_id| next_id.replace(next_id.get() + 1));
+    let callback: Callback = Box::new(move |result| {
+        if let Ok(result) = result.downcast::<Result<P::Device, Error>>() {
+            done(*result);
+        }
+    });
+    PENDING.with(|pending| pending.borrow_mut().insert(id, callback));
+
+    let background = dispatch.clone();
+    let spawned = thread::Builder::new()
+        .name(String::from("iced-nsview init"))
+        .spawn(move || {
+            let result = provider.create();
+            background(Box::new(move || deliver(id, Box::new(result))));
+        });
+    if spawned.is_err() {
+        let result: Result<P::Device, Error> = Err(Error::ThreadSpawnFailed);
+        dispatch(Box::new(move || deliver(id, Box::new(result))));
+    }
+
+    Request { id }
+}
+
+/// Runs work on a later iteration of the main run loop.
+#[cfg(target_os = "macos")]
+pub(crate) fn main_queue(work: Work) {
+    main_thread::run(move |_| work());
+}
+
+fn deliver(id: u64, result: Box<dyn Any + Send>) {
+    let callback = PENDING.with(|pending| pending.borrow_mut().remove(&id));
+    if let Some(callback) = callback {
+        callback(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::rc::Rc;
+    use std::sync::mpsc::{self, Receiver};
+
+    use iced_native::mouse;
+
+    struct Fake(Result<u32, Error>);
+
+    impl Provider for Fake {
+        type Device = u32;
+
+        fn create(self) -> Result<u32, Error> {
+            self.0
+        }
+    }
+
+    /// Returns a dispatcher, and the work it queues for the test thread.
+    fn queue() -> (impl Fn(Work) + Clone + Send + 'static, Receiver<Work>) {
+        let (sender, receiver) = mpsc::channel();
+        let dispatch = move |work: Work| {
+            let _ = sender.send(work);
+        };
+
+        (dispatch, receiver)
+    }
+
+    fn moved(x: f32) -> Event {
+        Event::Mouse(mouse::Event::CursorMoved { x, y: 0.0 })
+    }
+
+    #[test]
+    fn replays_queued_events_in_order() {
+        let mut init = Init::new("payload");
+        init.queue_event(moved(1.0));
+        init.queue_event(moved(2.0));
+
+        let (payload, result, events) = init.complete(Ok(7)).unwrap();
+
+        assert_eq!(payload, "payload");
+        assert_eq!(result, Ok(7));
+        assert_eq!(events, vec![moved(1.0), moved(2.0)]);
+    }
+
+    #[test]
+    fn completes_once() {
+        let mut init = Init::new(());
+        assert!(init.complete::<()>(Ok(())).is_some());
+
+        init.queue_event(moved(1.0));
+        assert!(init.complete::<()>(Ok(())).is_none());
+        assert!(init.complete::<()>(Err(Error::AdapterNotFound)).is_none());
+        assert!(!init.is_pending());
+        assert!(init.payload_mut().is_none());
+        assert_eq!(init.error(), None);
+    }
+
+    #[test]
+    fn keeps_the_error() {
+        let mut init = Init::new(());
+        init.queue_event(moved(1.0));
+
+        let (_, result, _) = init
+            .complete::<()>(Err(Error::DeviceRequestFailed))
+            .unwrap();
+
+        assert_eq!(result, Err(Error::DeviceRequestFailed));
+        assert_eq!(init.error(), Some(&Error::DeviceRequestFailed));
+        assert!(init.complete::<()>(Ok(())).is_none());
+    }
+
+    #[test]
+    fn keeps_the_error_of_a_failed_start() {
+        let mut init = Init::new(());
+        assert!(init.complete::<()>(Ok(())).is_some());
+
+        let panic = crate::poison::catch(|| panic!("view")).unwrap_err();
+        init.fail(Error::Panicked(panic.clone()));
+
+        assert_eq!(init.error(), Some(&Error::Panicked(panic)));
+        assert!(init.complete::<()>(Ok(())).is_none());
+    }
+
+    #[test]
+    fn delivers_on_the_spawning_thread() {
+        let (dispatch, queued) = queue();
+        let received = Rc::new(Cell::new(None));
+        let sink = Rc::clone(&received);
+
+        let _request = spawn(Fake(Ok(7)), dispatch, move |result| sink.set(Some(result)));
+        assert_eq!(received.take(), None);
+
+        queued.recv().unwrap()();
+        assert_eq!(received.take(), Some(Ok(7)));
+    }
+
+    #[test]
+    fn delivers_errors() {
+        let (dispatch, queued) = queue();
+        let received = Rc::new(Cell::new(None));
+        let sink = Rc::clone(&received);
+
+        let _request = spawn(Fake(Err(Error::AdapterNotFound)), dispatch, move |result| {
+            sink.set(Some(result))
+        });
+
+        queued.recv().unwrap()();
+        assert_eq!(received.take(), Some(Err(Error::AdapterNotFound)));
+    }
+
+    #[test]
+    fn dropping_the_request_cancels_the_callback() {
+        let (dispatch, queued) = queue();
+        let received = Rc::new(Cell::new(false));
+        let sink = Rc::clone(&received);
+
+        let request = spawn(Fake(Ok(7)), dispatch, move |_| sink.set(true));
+        drop(request);
+        assert_eq!(Rc::strong_count(&received), 1);
+
+        queued.recv().unwrap()();
+        assert!(!received.get());
+    }
+}
@@ -5,6 +5,11 @@
 //!
 //! You should implement your GUI using `Application` trait, then you can initialize `IcedView`
 //! with it.
+//!
+//! ## Platforms
+//!
+//! Views are only available on macOS. On other platforms, the crate builds without them, so the
+//! parts which don't depend on AppKit can be tested there.
 
 #![deny(
     missing_docs,
@@ -22,74 +27,335 @@
     unreachable_pub
 )]
 
+pub mod animation;
+pub mod clipboard;
+pub mod clock;
+pub mod coalesce;
+pub mod command;
+mod context;
+pub mod drag;
+pub mod drop;
+pub mod error;
+pub mod geometry;
+pub mod gpu;
+pub mod hit_test;
+#[cfg(any(target_os = "macos", test))]
+mod init;
+pub mod main_thread;
+pub mod measure;
+#[cfg(any(target_os = "macos", test))]
+mod platform;
+pub mod poison;
+#[cfg(any(target_os = "macos", test))]
+mod process;
+pub mod recovery;
+pub mod redraw;
+#[cfg(any(target_os = "macos", test))]
+mod render_thread;
+pub mod resize;
+pub mod tracking;
 pub mod widget;
 
+#[cfg(target_os = "macos")]
+use std::any::TypeId;
+#[cfg(target_os = "macos")]
+use std::cell::{Cell, RefCell};
+#[cfg(target_os = "macos")]
 use std::ffi::{c_void, CStr};
+#[cfg(target_os = "macos")]
 use std::marker::PhantomData;
+#[cfg(target_os = "macos")]
 use std::os::raw::c_char;
+#[cfg(target_os = "macos")]
 use std::path::PathBuf;
-
-use cocoa::appkit::{
-    NSEvent, NSEventModifierFlags, NSEventType, NSPasteboard, NSURLPboardType, NSView,
+#[cfg(target_os = "macos")]
+use std::rc::Rc;
+
+#[cfg(target_os = "macos")]
+use cocoa::appkit::{NSEvent, NSEventModifierFlags, NSEventType, NSURLPboardType, NSView};
+#[cfg(target_os = "macos")]
+use cocoa::base::{id, nil, BOOL, NO};
+#[cfg(target_os = "macos")]
+use cocoa::foundation::{
+    NSArray, NSAutoreleasePool, NSPoint, NSRect, NSSize, NSString, NSUInteger,
 };
-use cocoa::base::{id, nil, BOOL};
-use cocoa::foundation::{NSArray, NSPoint, NSRect, NSSize, NSString, NSUInteger};
 
+#[cfg(target_os = "macos")]
 use core_graphics::base::CGFloat;
+#[cfg(target_os = "macos")]
 use core_graphics::geometry::{CGPoint, CGRect};
 
-use iced_wgpu::{settings, wgpu, Backend, Renderer, Settings as RendererSettings};
+#[cfg(target_os = "macos")]
+use iced_wgpu::{wgpu, Primitive};
+use iced_wgpu::{Renderer, Settings as RendererSettings};
 
 pub use iced_wgpu::Viewport;
 
-use iced_native::{program, window, Clipboard, Debug, Element as NativeElement, Event};
+use iced_native::Element as NativeElement;
+#[cfg(target_os = "macos")]
+use iced_native::{program, window, Debug, Event};
 
 pub use iced_native::{
-    futures, keyboard, mouse, Align, Background, Color, Command, Font, HorizontalAlignment, Length,
-    Point, Rectangle, Size, Vector, VerticalAlignment,
+    futures, keyboard, mouse, Align, Background, Color, Font, HorizontalAlignment, Length, Point,
+    Rectangle, Size, Vector, VerticalAlignment,
 };
 
+#[cfg(target_os = "macos")]
 use objc::declare::ClassDecl;
-use objc::runtime::{Class, Sel, YES};
+#[cfg(target_os = "macos")]
+use objc::rc::WeakPtr;
+#[cfg(target_os = "macos")]
+use objc::runtime::{Class, Protocol, Sel, YES};
+#[cfg(target_os = "macos")]
 use objc::{class, msg_send, sel, sel_impl};
 
+#[cfg(target_os = "macos")]
 pub use objc::runtime::Object;
 
+#[doc(no_inline)]
+pub use command::Command;
+#[doc(no_inline)]
+pub use error::Error;
+#[doc(no_inline)]
+pub use geometry::{Fit, Geometry, ZoomMode};
+#[doc(no_inline)]
+pub use main_thread::MainThread;
 #[doc(no_inline)]
 pub use widget::*;
 
+#[cfg(target_os = "macos")]
+use clipboard::Pasteboard;
+#[cfg(target_os = "macos")]
+use context::Context;
+
 /// A composition of widgets.
 pub type Element<'a, M> = NativeElement<'a, M, Renderer>;
 
 /// Iced view which is a subclass of `NSView`.
+///
+/// Like the `MainThread` marker it's created with, it can't leave the main thread:
+///
+/// ```compile_fail
+/// use iced_nsview::{Application, Command, Element, IcedView, Text};
+///
+/// struct Hello;
+///
+/// impl Application for Hello {
+///     type Message = ();
+///
+///     fn update(&mut self, _message: ()) -> Command<()> {
+///         Command::none()
+///     }
+///
+///     fn view(&mut self) -> Element<'_, ()> {
+///         Text::new("Hello").into()
+///     }
+/// }
+///
+/// fn assert_send<T: Send>() {}
+///
+/// assert_send::<IcedView<Hello>>();
+/// ```
+#[cfg(target_os = "macos")]
 pub struct IcedView<A: 'static + Application> {
     object: *mut Object,
     _phantom_app: PhantomData<A>,
 }
 
+#[cfg(target_os = "macos")]
 impl<A: 'static + Application> IcedView<A> {
     const EVENT_HANDLER_IVAR: &'static str = "_event_handler";
-    const DID_EXIT_DRAG: &'static str = "_did_exit_drag";
+    const TRACKING_AREA_IVAR: &'static str = "_tracking_area";
+    const INIT_IVAR: &'static str = "_init";
+    const PANIC_HOOK_IVAR: &'static str = "_panic_hook";
 
     /// Constructor.
-    pub fn new(application: A, viewport: Viewport, settings: Settings) -> Self {
-        let object = unsafe { Self::init_nsview(viewport.physical_size()) };
-        let event_handler = EventHandler::new(application, object, viewport, settings);
+    ///
+    /// `size` is the size of the view in points. The scale factor is taken from the window of
+    /// the view once it's attached to one. Like every AppKit view, it must be created on the
+    /// main thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the view can't be created. Use `try_new` to handle the error instead.
+    pub fn new(main_thread: MainThread, application: A, size: Size, settings: Settings) -> Self {
+        Self::try_new(main_thread, application, size, settings)
+            .unwrap_or_else(|error| panic!("Can't create IcedView: {}", error))
+    }
+
+    /// Constructor which returns an error if the view can't be created.
+    ///
+    /// On error, the application is dropped and nothing else is left behind, so the host can
+    /// fall back to another user interface.
+    ///
+    /// The GPU device of the view is created in the background, so the view is shown right away,
+    /// filled with the background color of the application. See `set_init_handler`.
+    pub fn try_new(
+        _main_thread: MainThread,
+        application: A,
+        size: Size,
+        settings: Settings,
+    ) -> Result<Self, Error> {
+        platform::validate(size, &settings)?;
+
+        let geometry =
+            Geometry::new(size, unsafe { main_screen_scale_factor() }).with_zoom(settings.zoom);
+        let geometry = geometry.with_reference_size(application.reference_size());
+        let mut cocoa = Cocoa::<A> {
+            drop_types: &settings.drop_types,
+            _phantom_app: PhantomData,
+        };
+        let (object, layer) = platform::create(&mut cocoa, &geometry)?;
+        // Released in `dealloc`, after the application is dropped.
+        let panic_hook = platform::into_ivar(poison::HookGuard::new());
         unsafe {
-            (*object).set_ivar(
-                Self::EVENT_HANDLER_IVAR,
-                Box::into_raw(Box::new(event_handler)) as *mut c_void,
-            );
+            (*object).set_ivar(Self::PANIC_HOOK_IVAR, panic_hook);
+        }
+        // Shown until the first frame is rendered.
+        layer.set_background_color(application.background_color());
+
+        // A context shared with another view only leaves the render thread to create.
+        let shared = Context::shared(&settings);
+        let provider = if shared.is_none() || settings.render_thread {
+            Some(context::Provider::new(
+                layer.0,
+                settings.clone(),
+                shared.is_some(),
+            ))
+        } else {
+            None
+        };
+        // The result is delivered on a later iteration of the main run loop, once the view is
+        // set up.
+        let view = object as usize;
+        let request = provider.map(|provider| {
+            init::spawn(provider, init::main_queue, move |result| unsafe {
+                Self::start(view as *mut Object, result)
+            })
+        });
+        let is_ready = request.is_none();
+
+        let loading = Loading {
+            application,
+            settings,
+            geometry,
+            layer,
+            shared,
+            resize_handler: None,
+            init_handler: None,
+            _request: request,
         };
+        let init = platform::into_ivar(init::Init::new(loading));
+        unsafe {
+            (*object).set_ivar(Self::INIT_IVAR, init);
+
+            if is_ready {
+                let contexts = context::Contexts {
+                    main: None,
+                    render: None,
+                };
+                Self::start(object, Ok(contexts));
+            }
+        }
 
-        Self {
+        Ok(Self {
             object,
             _phantom_app: PhantomData,
+        })
+    }
+
+    /// Starts the view with the contexts created in the background, or records why they
+    /// couldn't be created.
+    unsafe fn start(this: *mut Object, result: Result<context::Contexts, Error>) {
+        main_thread::assert_main_thread();
+        let init = match Self::init(&*this) {
+            Some(init) => init,
+            None => return,
+        };
+        let result = match init.payload_mut() {
+            Some(loading) => {
+                let view = this as usize;
+                result.and_then(|contexts| {
+                    loading.take_contexts(contexts, move || {
+                        Self::on_render_thread_recovery(view as *mut Object)
+                    })
+                })
+            }
+            None => return,
+        };
+        let (loading, result, events) = match init.complete(result) {
+            Some(completion) => completion,
+            None => return,
+        };
+
+        let Loading {
+            application,
+            settings,
+            geometry,
+            layer,
+            resize_handler,
+            init_handler,
+            ..
+        } = loading;
+        let (context, render_thread) = match result {
+            Ok(contexts) => contexts,
+            Err(error) => {
+                // The layer keeps showing the background color.
+                if let Some(init_handler) = init_handler {
+                    init_handler(Err(error));
+                }
+                return;
+            }
+        };
+
+        let activation = settings.tracking;
+        let started = poison::catch(|| {
+            EventHandler::new(
+                application,
+                this,
+                geometry,
+                settings,
+                layer,
+                context,
+                render_thread,
+            )
+        });
+        let mut event_handler = match started {
+            Ok(event_handler) => event_handler,
+            Err(panic) => {
+                // The application is gone, so the view can't be recovered.
+                let error = Error::Panicked(panic);
+                init.fail(error.clone());
+                if let Some(init_handler) = init_handler {
+                    init_handler(Err(error));
+                }
+                return;
+            }
+        };
+        event_handler.resize_handler = resize_handler;
+        let event_handler = platform::into_ivar(event_handler);
+        (*this).set_ivar(Self::EVENT_HANDLER_IVAR, event_handler);
+        Self::install_tracking_area(&mut *this, activation);
+
+        // The events received in the meantime are handled before the first frame.
+        Self::with_event_handler(&*this, (), |event_handler| {
+            event_handler.queue_event(events);
+            event_handler.fit_content();
+            event_handler.process_queue();
+        });
+        let () = msg_send![this, invalidateIntrinsicContentSize];
+        let () = msg_send![this, setNeedsDisplay: YES];
+
+        if let Some(init_handler) = init_handler {
+            init_handler(Ok(()));
         }
     }
 
-    unsafe fn init_nsview(size: Size<u32>) -> *mut Object {
-        let class = Self::declare_class();
+    unsafe fn init_nsview(
+        size: Size,
+        drop_types: &[clipboard::Kind],
+    ) -> Result<*mut Object, Error> {
+        let class = Self::declare_class()?;
         let rect = NSRect::new(
             NSPoint::new(0.0, 0.0),
             NSSize::new(size.width.into(), size.height.into()),
@@ -98,18 +364,48 @@ impl<A: 'static + Application> IcedView<A> {
         let object: *mut Object = msg_send![allocation, initWithFrame: rect];
         // NSViewLayerContentsRedrawDuringViewResize
         let () = msg_send![object, setLayerContentsRedrawPolicy: 2];
-        let types = NSArray::arrayWithObject(nil, NSURLPboardType);
+        let types = drop_types
+            .iter()
+            .flat_map(|kind| match kind {
+                clipboard::Kind::FileUrls => vec![NSURLPboardType, nsstring(kind.uti())],
+                _ => vec![nsstring(kind.uti())],
+            })
+            .collect::<Vec<_>>();
+        let types = NSArray::arrayWithObjects(nil, &types);
         let () = msg_send![object, registerForDraggedTypes: types];
 
-        object
+        Ok(object)
+    }
+
+    /// Returns the name of the class, which is unique to the application type and to the
+    /// binary, as the methods of the class are.
+    fn class_name() -> String {
+        // The address of this function differs for each application type, and for each binary
+        // loaded in the process, e.g. two plugins built with the same version of a crate.
+        platform::class_name(std::any::type_name::<A>(), Self::class_name as usize)
+    }
+
+    /// Returns the class of the views of the application type, registered by the first view.
+    unsafe fn declare_class() -> Result<&'static Class, Error> {
+        platform::CLASSES.with(|classes| {
+            classes
+                .borrow_mut()
+                .get_or_register(TypeId::of::<A>(), || Self::register_class())
+        })
     }
 
-    unsafe fn declare_class() -> &'static Class {
+    /// Registers the class, unless the runtime already has a class with its name.
+    unsafe fn register_class() -> Option<&'static Class> {
         let superclass = class!(NSView);
-        let mut decl =
-            ClassDecl::new("IcedView", superclass).expect("Can't declare IcedView class.");
+        let name = Self::class_name();
+        let mut decl = ClassDecl::new(&name, superclass)?;
         decl.add_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
-        decl.add_ivar::<bool>(Self::DID_EXIT_DRAG);
+        decl.add_ivar::<*mut c_void>(Self::TRACKING_AREA_IVAR);
+        decl.add_ivar::<*mut c_void>(Self::INIT_IVAR);
+        decl.add_ivar::<*mut c_void>(Self::PANIC_HOOK_IVAR);
+
+        let dealloc: extern "C" fn(&mut Object, Sel) = Self::dealloc;
+        decl.add_method(sel!(dealloc), dealloc);
 
         let accepts_first_responder: extern "C" fn(&Object, Sel) -> BOOL =
             Self::accepts_first_responder;
@@ -118,7 +414,18 @@ impl<A: 'static + Application> IcedView<A> {
         let is_flipped: extern "C" fn(&Object, Sel) -> BOOL = Self::is_flipped;
         decl.add_method(sel!(isFlipped), is_flipped);
 
-        let update_tracking_areas: extern "C" fn(&Object, Sel) = Self::update_tracking_areas;
+        let intrinsic_content_size: extern "C" fn(&Object, Sel) -> NSSize =
+            Self::intrinsic_content_size;
+        decl.add_method(sel!(intrinsicContentSize), intrinsic_content_size);
+
+        let hit_test: extern "C" fn(&Object, Sel, NSPoint) -> *mut Object = Self::hit_test;
+        decl.add_method(sel!(hitTest:), hit_test);
+
+        let accepts_first_mouse: extern "C" fn(&Object, Sel, *mut Object) -> BOOL =
+            Self::accepts_first_mouse;
+        decl.add_method(sel!(acceptsFirstMouse:), accepts_first_mouse);
+
+        let update_tracking_areas: extern "C" fn(&mut Object, Sel) = Self::update_tracking_areas;
         decl.add_method(sel!(updateTrackingAreas), update_tracking_areas);
 
         let update_layer: extern "C" fn(&mut Object, Sel) = Self::update_layer;
@@ -127,15 +434,51 @@ impl<A: 'static + Application> IcedView<A> {
         let resize: extern "C" fn(&mut Object, Sel) = Self::resize;
         decl.add_method(sel!(viewWillStartLiveResize), resize);
         decl.add_method(sel!(viewDidEndLiveResize), resize);
+        decl.add_method(sel!(viewDidChangeBackingProperties), resize);
+        decl.add_method(sel!(viewDidMoveToWindow), resize);
+
+        let set_frame_size: extern "C" fn(&mut Object, Sel, NSSize) = Self::set_frame_size;
+        decl.add_method(sel!(setFrameSize:), set_frame_size);
 
         let dragging_entered: extern "C" fn(&mut Object, Sel, *mut Object) -> NSUInteger =
             Self::dragging_entered;
         decl.add_method(sel!(draggingEntered:), dragging_entered);
+        let dragging_updated: extern "C" fn(&mut Object, Sel, *mut Object) -> NSUInteger =
+            Self::dragging_updated;
+        decl.add_method(sel!(draggingUpdated:), dragging_updated);
+        let perform_drag_operation: extern "C" fn(&mut Object, Sel, *mut Object) -> BOOL =
+            Self::perform_drag_operation;
+        decl.add_method(sel!(performDragOperation:), perform_drag_operation);
         let dragging_ended: extern "C" fn(&mut Object, Sel, *mut Object) = Self::dragging_ended;
         decl.add_method(sel!(draggingEnded:), dragging_ended);
         let dragging_exited: extern "C" fn(&mut Object, Sel, *mut Object) = Self::dragging_exited;
         decl.add_method(sel!(draggingExited:), dragging_exited);
 
+        if let Some(protocol) = Protocol::get("NSDraggingSource") {
+            decl.add_protocol(protocol);
+        }
+        let source_operation_mask: extern "C" fn(
+            &Object,
+            Sel,
+            *mut Object,
+            NSUInteger,
+        ) -> NSUInteger = Self::source_operation_mask;
+        decl.add_method(
+            sel!(draggingSession:sourceOperationMaskForDraggingContext:),
+            source_operation_mask,
+        );
+        let dragging_session_ended: extern "C" fn(
+            &mut Object,
+            Sel,
+            *mut Object,
+            NSPoint,
+            NSUInteger,
+        ) = Self::dragging_session_ended;
+        decl.add_method(
+            sel!(draggingSession:endedAtPoint:operation:),
+            dragging_session_ended,
+        );
+
         let handle_event: extern "C" fn(&mut Object, Sel, *mut Object) = Self::handle_event;
         decl.add_method(sel!(mouseDown:), handle_event);
         decl.add_method(sel!(mouseUp:), handle_event);
@@ -150,439 +493,1956 @@ impl<A: 'static + Application> IcedView<A> {
         decl.add_method(sel!(keyUp:), handle_event);
         decl.add_method(sel!(flagsChanged:), handle_event);
 
-        decl.register()
+        Some(decl.register())
     }
 
     extern "C" fn accepts_first_responder(_this: &Object, _cmd: Sel) -> BOOL {
+        main_thread::assert_main_thread();
         return YES;
     }
 
     extern "C" fn is_flipped(_this: &Object, _cmd: Sel) -> BOOL {
+        main_thread::assert_main_thread();
         return YES;
     }
 
-    extern "C" fn update_tracking_areas(this: &Object, _cmd: Sel) {
-        // NSTrackingMouseEnteredAndExited | NSTrackingMouseMoved | NSTrackingCursorUpdate |
-        // NSTrackingActiveInKeyWindow
-        let options = 0x01 | 0x02 | 0x04 | 0x20;
-        let class = class!(NSTrackingArea);
+    /// Frees the event handler along with the view, whoever releases the view last.
+    ///
+    /// A view deallocated before its GPU device is created cancels the initialization.
+    extern "C" fn dealloc(this: &mut Object, _cmd: Sel) {
+        main_thread::assert_main_thread();
+        unsafe {
+            let event_handler: *mut *mut c_void = this.get_mut_ivar(Self::EVENT_HANDLER_IVAR);
+            let init: *mut *mut c_void = this.get_mut_ivar(Self::INIT_IVAR);
+            let panic_hook: *mut *mut c_void = this.get_mut_ivar(Self::PANIC_HOOK_IVAR);
+            platform::free_ivars::<EventHandler<A>, init::Init<Loading<A>>, poison::HookGuard>(
+                &mut *event_handler,
+                &mut *init,
+                &mut *panic_hook,
+            );
+
+            let () = msg_send![super(this, class!(NSView)), dealloc];
+        }
+    }
+
+    /// Returns the initialization of the view.
+    unsafe fn init<'a>(this: &Object) -> Option<&'a mut init::Init<Loading<A>>> {
+        let value = this.get_ivar::<*mut c_void>(Self::INIT_IVAR);
+
+        (*value as *mut init::Init<Loading<A>>).as_mut()
+    }
+
+    /// Returns what the view holds until its GPU device is created, unless it's started.
+    unsafe fn loading<'a>(this: &Object) -> Option<&'a mut Loading<A>> {
+        Self::init(this).and_then(|init| init.payload_mut())
+    }
+
+    /// Returns the event handler, if the view is started.
+    unsafe fn event_handler<'a>(this: &Object) -> Option<&'a mut EventHandler<A>> {
+        let value = this.get_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
+
+        (*value as *mut EventHandler<A>).as_mut()
+    }
+
+    /// Runs `f` with the event handler, unless it isn't set yet or the view is poisoned.
+    ///
+    /// A panic in `f` poisons the view instead of unwinding into AppKit. Returns `default` if
+    /// `f` didn't run or panicked.
+    unsafe fn with_event_handler<R>(
+        this: &Object,
+        default: R,
+        f: impl FnOnce(&mut EventHandler<A>) -> R,
+    ) -> R {
+        let value = this.get_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
+        let event_handler = *value as *mut EventHandler<A>;
+        if event_handler.is_null() || (*event_handler).poison.is_poisoned() {
+            return default;
+        }
+
+        match poison::catch(|| f(&mut *event_handler)) {
+            Ok(value) => value,
+            Err(panic) => {
+                (*event_handler).poison.poison(panic);
+                let () = msg_send![this, setNeedsDisplay: YES];
+                default
+            }
+        }
+    }
+
+    extern "C" fn intrinsic_content_size(this: &Object, _cmd: Sel) -> NSSize {
+        main_thread::assert_main_thread();
+        // NSViewNoIntrinsicMetric
+        let no_metric = -1.0;
+        let size = unsafe {
+            Self::with_event_handler(this, None, |event_handler| {
+                Some(event_handler.intrinsic_content_size())
+            })
+        };
+        let size = match size {
+            Some(size) => size,
+            None => return NSSize::new(no_metric, no_metric),
+        };
+        let dimension = |value: f32| {
+            if value.is_finite() {
+                value.into()
+            } else {
+                no_metric
+            }
+        };
+
+        NSSize::new(dimension(size.width), dimension(size.height))
+    }
+
+    extern "C" fn hit_test(this: &Object, _cmd: Sel, point: NSPoint) -> *mut Object {
+        main_thread::assert_main_thread();
+        unsafe {
+            let hit: *mut Object = msg_send![super(this, class!(NSView)), hitTest: point];
+            let this_ptr = this as *const Object as *mut Object;
+            // Subviews and points outside of the view are resolved by AppKit.
+            if hit != this_ptr {
+                return hit;
+            }
+
+            // The point is in the coordinate system of the superview.
+            let superview: *mut Object = msg_send![this, superview];
+            let location = NSView::convertPoint_fromView_(this_ptr, point, superview);
+            let is_hit = Self::with_event_handler(this, true, |event_handler| {
+                event_handler.hit_test(Point::new(location.x as f32, location.y as f32))
+            });
+
+            if is_hit {
+                hit
+            } else {
+                nil
+            }
+        }
+    }
+
+    extern "C" fn accepts_first_mouse(this: &Object, _cmd: Sel, _event: *mut Object) -> BOOL {
+        main_thread::assert_main_thread();
+        let first_mouse = unsafe {
+            let default = Self::loading(this)
+                .map(|loading| loading.settings.first_mouse)
+                .unwrap_or_default();
+            Self::with_event_handler(this, default, |event_handler| event_handler.first_mouse)
+        };
+
+        match first_mouse {
+            tracking::FirstMouse::Accept => YES,
+            tracking::FirstMouse::Ignore => NO,
+        }
+    }
+
+    extern "C" fn update_tracking_areas(this: &mut Object, _cmd: Sel) {
+        main_thread::assert_main_thread();
         unsafe {
-            let bounds: NSRect = msg_send![this, bounds];
-            let alloc: *mut Object = msg_send![class, alloc];
-            let tracking_area: *mut Object =
-                msg_send![alloc, initWithRect:bounds options:options owner:this userInfo:nil];
-            let () = msg_send![this, addTrackingArea: tracking_area];
+            let () = msg_send![super(this, class!(NSView)), updateTrackingAreas];
+
+            // The tracking area follows the visible rect of the view, so it's only installed
+            // once, unless the activation changes.
+            if !this
+                .get_ivar::<*mut c_void>(Self::TRACKING_AREA_IVAR)
+                .is_null()
+            {
+                return;
+            }
+
+            let default = Self::loading(this)
+                .map(|loading| loading.settings.tracking)
+                .unwrap_or_default();
+            let activation =
+                Self::with_event_handler(this, default, |event_handler| event_handler.activation);
+
+            Self::install_tracking_area(this, activation);
         }
     }
 
+    unsafe fn install_tracking_area(this: &mut Object, activation: tracking::Activation) {
+        let previous = *this.get_ivar::<*mut c_void>(Self::TRACKING_AREA_IVAR) as *mut Object;
+        if !previous.is_null() {
+            let () = msg_send![this, removeTrackingArea: previous];
+        }
+
+        let class = class!(NSTrackingArea);
+        let bounds: NSRect = msg_send![this, bounds];
+        let options = activation.options() as NSUInteger;
+        let alloc: *mut Object = msg_send![class, alloc];
+        let tracking_area: *mut Object =
+            msg_send![alloc, initWithRect:bounds options:options owner:&*this userInfo:nil];
+        let () = msg_send![this, addTrackingArea: tracking_area];
+        // The view keeps the tracking area alive from now on.
+        let () = msg_send![tracking_area, release];
+
+        this.set_ivar(Self::TRACKING_AREA_IVAR, tracking_area as *mut c_void);
+    }
+
     extern "C" fn update_layer(this: &mut Object, cmd: Sel) {
+        main_thread::assert_main_thread();
         unsafe {
             let in_resize: BOOL = msg_send![this, inLiveResize];
             if in_resize != 0 {
                 Self::resize(this, cmd);
             }
 
-            let value = this.get_mut_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
+            Self::with_event_handler(this, (), |event_handler| event_handler.redraw());
+
+            // A poisoned view shows what happened instead of the user interface.
+            let value = this.get_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
             let event_handler = *value as *mut EventHandler<A>;
-            (*event_handler).redraw();
+            if !event_handler.is_null() && (*event_handler).poison.is_poisoned() {
+                let _ = poison::catch(|| (*event_handler).redraw_poisoned());
+            }
         }
     }
 
     extern "C" fn resize(this: &mut Object, _cmd: Sel) {
+        main_thread::assert_main_thread();
         unsafe {
-            let value = this.get_mut_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
-            let event_handler = *value as *mut EventHandler<A>;
             let this_ptr: *mut Object = this;
             let bounds = NSView::bounds(this_ptr);
-            let parent_window: *mut Object = msg_send![this, window];
-            let scale_factor: CGFloat = msg_send![parent_window, backingScaleFactor];
-            (*event_handler).resize(
-                Size::new(bounds.size.width as u32, bounds.size.height as u32),
-                scale_factor,
-            );
+            let size = Size::new(bounds.size.width as f32, bounds.size.height as f32);
+            let scale_factor = get_nsview_scale_factor(this_ptr as *mut c_void);
+            let display = get_nsview_display(this_ptr);
+            let update = |geometry: Geometry| {
+                // Without a window the scale factor is unknown, so the current one is kept.
+                let geometry = geometry.with_size(size);
+                match scale_factor {
+                    Some(scale_factor) => geometry.with_scale_factor(scale_factor),
+                    None => geometry,
+                }
+            };
+
+            // Hosts may resize the view before its GPU device is created.
+            if let Some(loading) = Self::loading(this) {
+                let geometry = update(loading.geometry);
+                if geometry.scale_factor() != loading.geometry.scale_factor() {
+                    loading.layer.set_contents_scale(geometry.scale_factor());
+                }
+                loading.geometry = geometry;
+            }
+
+            Self::with_event_handler(this, (), |event_handler| {
+                event_handler.resize(update(event_handler.geometry));
+                if let Some(display) = display {
+                    event_handler.set_display(display);
+                }
+                event_handler.process_queue();
+            });
+        }
+    }
+
+    extern "C" fn set_frame_size(this: &mut Object, cmd: Sel, size: NSSize) {
+        main_thread::assert_main_thread();
+        unsafe {
+            let () = msg_send![super(this, class!(NSView)), setFrameSize: size];
+        }
+
+        Self::resize(this, cmd);
+    }
+
+    extern "C" fn dragging_entered(this: &mut Object, cmd: Sel, sender: *mut Object) -> NSUInteger {
+        main_thread::assert_main_thread();
+        unsafe {
+            let pasteboard: id = msg_send![sender, draggingPasteboard];
+            let operations: NSUInteger = msg_send![sender, draggingSourceOperationMask];
+
+            Self::with_event_handler(this, (), |event_handler| {
+                event_handler.on_drag_entered(
+                    Pasteboard::from_raw(pasteboard),
+                    drag::Operation::from_bits(operations as u64),
+                );
+            });
+        }
+
+        Self::dragging_updated(this, cmd, sender)
+    }
+
+    extern "C" fn dragging_updated(
+        this: &mut Object,
+        _cmd: Sel,
+        sender: *mut Object,
+    ) -> NSUInteger {
+        main_thread::assert_main_thread();
+        unsafe {
+            let location: NSPoint = msg_send![sender, draggingLocation];
+            let location = NSView::convertPoint_fromView_(this, location, nil);
+
+            let operation =
+                Self::with_event_handler(this, drag::Operation::NONE, |event_handler| {
+                    let operation = event_handler
+                        .on_drag_updated(Point::new(location.x as f32, location.y as f32));
+                    event_handler.process_queue();
+
+                    operation
+                });
+
+            operation.bits() as NSUInteger
+        }
+    }
+
+    extern "C" fn perform_drag_operation(
+        this: &mut Object,
+        _cmd: Sel,
+        _sender: *mut Object,
+    ) -> BOOL {
+        main_thread::assert_main_thread();
+        unsafe {
+            let accepted = Self::with_event_handler(this, false, |event_handler| {
+                let accepted = event_handler.on_drag_performed();
+                event_handler.process_queue();
+
+                accepted
+            });
+
+            if accepted {
+                YES
+            } else {
+                NO
+            }
+        }
+    }
+
+    extern "C" fn dragging_ended(this: &mut Object, _cmd: Sel, _sender: *mut Object) {
+        main_thread::assert_main_thread();
+        unsafe {
+            Self::with_event_handler(this, (), |event_handler| event_handler.on_drag_ended());
+        }
+    }
+
+    extern "C" fn dragging_exited(this: &mut Object, _cmd: Sel, _sender: *mut Object) {
+        main_thread::assert_main_thread();
+        unsafe {
+            Self::with_event_handler(this, (), |event_handler| {
+                event_handler.on_drag_exited();
+                event_handler.process_queue();
+            });
+        }
+    }
+
+    extern "C" fn source_operation_mask(
+        this: &Object,
+        _cmd: Sel,
+        _session: *mut Object,
+        _context: NSUInteger,
+    ) -> NSUInteger {
+        main_thread::assert_main_thread();
+        // AppKit asks again as the modifiers change during the session.
+        let operations = unsafe {
+            let flags: NSUInteger = msg_send![class!(NSEvent), modifierFlags];
+            let modifiers = keyboard::ModifiersState::from(ModifierFlags(
+                NSEventModifierFlags::from_bits_truncate(flags),
+            ));
+
+            Self::with_event_handler(this, drag::Operation::NONE, |event_handler| {
+                drag::modified(event_handler.drag_source.operations(), modifiers)
+            })
+        };
+
+        operations.bits() as NSUInteger
+    }
+
+    extern "C" fn dragging_session_ended(
+        this: &mut Object,
+        _cmd: Sel,
+        _session: *mut Object,
+        _point: NSPoint,
+        operation: NSUInteger,
+    ) {
+        main_thread::assert_main_thread();
+        unsafe {
+            Self::with_event_handler(this, (), |event_handler| {
+                event_handler.end_drag(drag::Operation::from_bits(operation as u64));
+                event_handler.process_queue();
+            });
+        }
+    }
+
+    extern "C" fn handle_event(this: &mut Object, _cmd: Sel, event: *mut Object) {
+        main_thread::assert_main_thread();
+        unsafe {
+            let view: *mut Object = this;
+            if let Some(init) = Self::init(this) {
+                if let Some(loading) = init.payload_mut() {
+                    let events: Vec<Event> = NSEventT {
+                        raw_event: event,
+                        view,
+                        geometry: loading.geometry,
+                    }
+                    .into();
+                    events.into_iter().for_each(|event| init.queue_event(event));
+                    return;
+                }
+            }
+
+            Self::with_event_handler(this, (), |event_handler| {
+                event_handler.track_mouse_event(event);
+                let geometry = event_handler.geometry;
+                event_handler.queue_event(
+                    NSEventT {
+                        raw_event: event,
+                        view,
+                        geometry,
+                    }
+                    .into(),
+                );
+                event_handler.process_queue();
+            });
+        };
+    }
+
+    /// Called on the main thread when the render thread of the view recovered.
+    unsafe fn on_render_thread_recovery(this: *mut Object) {
+        main_thread::assert_main_thread();
+        Self::with_event_handler(&*this, (), |event_handler| {
+            event_handler.process_queue();
+        });
+    }
+
+    /// Called on the main thread with the device recreated after a failure.
+    unsafe fn on_device_created(this: *mut Object, result: Result<context::Contexts, Error>) {
+        main_thread::assert_main_thread();
+        Self::with_event_handler(&*this, (), |event_handler| {
+            event_handler.on_device_created(result)
+        });
+    }
+
+    /// Called on the main thread by the display link of the view.
+    unsafe fn on_display_link(this: *mut Object) {
+        main_thread::assert_main_thread();
+        Self::with_event_handler(&*this, (), |event_handler| {
+            if event_handler.on_display_frame() {
+                event_handler.process_queue();
+            }
+        });
+    }
+
+    /// Get a weak reference to the Cocoa view.
+    pub fn handle(&self) -> Handle {
+        Handle {
+            object: unsafe { WeakPtr::new(self.object) },
+        }
+    }
+
+    /// Get a raw pointer to the Cocoa view.
+    pub fn raw_object(&self) -> *mut Object {
+        self.object
+    }
+
+    /// Make this view a subview of another view.
+    pub unsafe fn make_subview_of(&self, view: *mut c_void) {
+        NSView::addSubview_(view as id, self.object);
+    }
+
+    /// Change when the view tracks the mouse.
+    pub fn set_tracking(&self, activation: tracking::Activation) {
+        unsafe {
+            let this = self.object.as_mut().unwrap();
+            if let Some(event_handler) = Self::event_handler(this) {
+                event_handler.activation = activation;
+            } else if let Some(loading) = Self::loading(this) {
+                loading.settings.tracking = activation;
+            }
+
+            Self::install_tracking_area(this, activation);
+        }
+    }
+
+    /// Change whether the click activating the window of the view is handled by the view.
+    pub fn set_first_mouse(&self, first_mouse: tracking::FirstMouse) {
+        unsafe {
+            let this = self.object.as_ref().unwrap();
+            if let Some(event_handler) = Self::event_handler(this) {
+                event_handler.first_mouse = first_mouse;
+            } else if let Some(loading) = Self::loading(this) {
+                loading.settings.first_mouse = first_mouse;
+            }
+        }
+    }
+
+    /// Change the zoom of the user interface, independently of the scale factor of the display.
+    ///
+    /// With `ZoomMode::KeepLayout`, the view is resized so the widgets keep the same logical
+    /// size.
+    pub fn set_zoom(&self, zoom: f64, mode: ZoomMode) {
+        unsafe {
+            let this = self.object.as_ref().unwrap();
+            let current = match (Self::event_handler(this), Self::loading(this)) {
+                (Some(event_handler), _) => event_handler.geometry,
+                (None, Some(loading)) => loading.geometry,
+                (None, None) => return,
+            };
+            let zoomed = current.with_zoom(zoom);
+            let zoomed = match mode {
+                ZoomMode::KeepSize => zoomed,
+                ZoomMode::KeepLayout => {
+                    let logical_size = current.logical_size();
+                    let size =
+                        zoomed.to_points(Point::new(logical_size.width, logical_size.height));
+                    zoomed.with_size(Size::new(size.x, size.y))
+                }
+            };
+            if let Some(event_handler) = Self::event_handler(this) {
+                event_handler.resize(zoomed);
+            } else if let Some(loading) = Self::loading(this) {
+                loading.geometry = zoomed;
+            }
+
+            if zoomed.size() != current.size() {
+                let size = zoomed.size();
+                let size = NSSize::new(size.width.into(), size.height.into());
+                // The view is already resized, so the resulting resize is a no-op.
+                let () = msg_send![self.object, setFrameSize: size];
+            }
+
+            let () = msg_send![self.object, setNeedsDisplay: YES];
         }
     }
 
-    extern "C" fn dragging_entered(
-        this: &mut Object,
-        _cmd: Sel,
-        sender: *mut Object,
-    ) -> NSUInteger {
-        unsafe {
-            this.set_ivar::<bool>(Self::DID_EXIT_DRAG, false);
+    /// Returns the panic which poisoned the view, if any.
+    ///
+    /// A poisoned view ignores events and shows an error screen instead of the user interface.
+    /// A view which panicked while it started, before it could show the error screen, returns
+    /// the panic too.
+    pub fn panic(&self) -> Option<poison::Panic> {
+        unsafe {
+            let this = self.object.as_ref().unwrap();
+            if let Some(event_handler) = Self::event_handler(this) {
+                return event_handler.poison.panic().cloned();
+            }
+
+            match Self::init(this)?.error()? {
+                Error::Panicked(panic) => Some(panic.clone()),
+                _ => None,
+            }
+        }
+    }
+
+    /// Replace the application of the view, which brings a poisoned view back to life.
+    ///
+    /// Returns the panic which poisoned the view, if any. A view whose GPU device couldn't be
+    /// created, or which panicked before its event handler was set up, drops the application.
+    pub fn recover(&self, application: A) -> Option<poison::Panic> {
+        unsafe {
+            let this = self.object.as_ref().unwrap();
+            if let Some(loading) = Self::loading(this) {
+                loading.application = application;
+                return None;
+            }
+
+            let event_handler = Self::event_handler(this)?;
+            let panic = event_handler.recover(application);
+            let () = msg_send![self.object, setNeedsDisplay: YES];
+
+            panic
+        }
+    }
+
+    /// Render a new frame, even if nothing visible changed.
+    ///
+    /// The view only renders when its content or its geometry changes, which covers everything
+    /// the widgets draw.
+    pub fn request_redraw(&self) {
+        unsafe {
+            if let Some(event_handler) = Self::event_handler(self.object.as_ref().unwrap()) {
+                event_handler.redraw_tracker.request();
+            }
+            let () = msg_send![self.object, setNeedsDisplay: YES];
+        }
+    }
+
+    /// Returns how many pointer events the view received, and how many of them were merged.
+    ///
+    /// The events received before the GPU device of the view is created are counted once it's
+    /// created.
+    pub fn coalescing_statistics(&self) -> coalesce::Statistics {
+        unsafe {
+            Self::event_handler(self.object.as_ref().unwrap())
+                .map(|event_handler| event_handler.coalescer.statistics())
+                .unwrap_or_default()
+        }
+    }
+
+    /// Returns the size in points closest to `proposed` which the application supports.
+    ///
+    /// Hosts which negotiate the size of the view can call it before resizing the view.
+    pub fn constrain_size(&self, proposed: Size) -> Size {
+        unsafe {
+            let this = self.object.as_ref().unwrap();
+            if let Some(event_handler) = Self::event_handler(this) {
+                event_handler.constrain_size(proposed)
+            } else if let Some(loading) = Self::loading(this) {
+                constrain_size(
+                    loading.geometry,
+                    &loading.application.size_hints(),
+                    proposed,
+                )
+            } else {
+                proposed
+            }
+        }
+    }
+
+    /// Set the function asking the host to resize the view, with a size in points.
+    ///
+    /// It's called when the application requests a resize. Without it, the view resizes itself,
+    /// along with its window if it's the content view.
+    pub fn set_resize_handler(&self, f: impl FnMut(Size) + 'static) {
+        unsafe {
+            let this = self.object.as_ref().unwrap();
+            if let Some(event_handler) = Self::event_handler(this) {
+                event_handler.resize_handler = Some(Box::new(f));
+            } else if let Some(loading) = Self::loading(this) {
+                loading.resize_handler = Some(Box::new(f));
+            }
+        }
+    }
+
+    /// Set the function called once the GPU device of the view is created, or with the error
+    /// which prevented it.
+    ///
+    /// Until then, the view shows the background color of the application and queues the
+    /// events it receives. If the device is already created, or couldn't be, `f` is called
+    /// right away.
+    pub fn set_init_handler(&self, f: impl FnOnce(Result<(), Error>) + 'static) {
+        let result = unsafe {
+            let init = match Self::init(self.object.as_ref().unwrap()) {
+                Some(init) => init,
+                None => return,
+            };
+            if let Some(loading) = init.payload_mut() {
+                loading.init_handler = Some(Box::new(f));
+                return;
+            }
+
+            match init.error() {
+                Some(error) => Err(error.clone()),
+                None => Ok(()),
+            }
+        };
+
+        f(result);
+    }
+
+    /// Returns the error which prevented the creation of the GPU device of the view, or the
+    /// start of the view once it was created, if any.
+    pub fn init_error(&self) -> Option<Error> {
+        unsafe { Self::init(self.object.as_ref().unwrap()).and_then(|init| init.error().cloned()) }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl<A: 'static + Application> Drop for IcedView<A> {
+    /// Releases the view. The application is dropped along with the view, once the host
+    /// releases it too.
+    fn drop(&mut self) {
+        unsafe {
+            let () = msg_send![self.object, release];
+        }
+    }
+}
+
+/// A weak reference to the Cocoa view of an `IcedView`.
+///
+/// Unlike `IcedView`, it doesn't keep the view alive, so it tells whether the host still holds
+/// the view.
+#[cfg(target_os = "macos")]
+pub struct Handle {
+    object: WeakPtr,
+}
+
+#[cfg(target_os = "macos")]
+impl Handle {
+    /// Returns `true` if the view hasn't been deallocated.
+    pub fn is_alive(&self) -> bool {
+        !self.object.load().is_null()
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl std::fmt::Debug for Handle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Handle")
+            .field("is_alive", &self.is_alive())
+            .finish()
+    }
+}
+
+/// Implement this trait for your application then pass it into `IcedView::new`.
+pub trait Application {
+    /// The message your application will produce.
+    type Message: Clone + std::fmt::Debug + Send;
+
+    /// Message processing function.
+    fn update(&mut self, message: Self::Message) -> Command<Self::Message>;
+
+    /// Application interface.
+    fn view(&mut self) -> Element<'_, Self::Message>;
+
+    /// Returns the background color of the [`Application`].
+    ///
+    /// By default, it returns `Color::WHITE`.
+    fn background_color(&self) -> Color {
+        Color::WHITE
+    }
+
+    /// Returns the size the user interface is designed at.
+    ///
+    /// If a size is returned, the user interface is scaled uniformly to fit the view instead of
+    /// reflowing, and `Settings::fit` decides what happens to the remaining space.
+    ///
+    /// By default, it returns `None`.
+    fn reference_size(&self) -> Option<Size> {
+        None
+    }
+
+    /// Returns the operation to perform if the content dragged over the view is dropped at the
+    /// given position.
+    ///
+    /// Return `Operation::NONE` to refuse the drop. If several operations are returned, the
+    /// preferred one allowed by the source is chosen.
+    ///
+    /// By default, files are copied and any other content is refused.
+    fn drop_operation(&self, payload: &drop::Payload, _position: Point) -> drag::Operation {
+        if payload.files().is_empty() {
+            drag::Operation::NONE
+        } else {
+            drag::Operation::COPY
+        }
+    }
+
+    /// Produces a message from an event of the content dragged over the view.
+    ///
+    /// Dragged files are reported as window events in any case.
+    ///
+    /// By default, it returns `None`.
+    fn on_drop(&self, _event: drop::Event) -> Option<Self::Message> {
+        None
+    }
+
+    /// Returns whether a click at the given position is handled by the view, or falls through
+    /// to the views underneath.
+    ///
+    /// If `None` is returned, `Settings::hit_testing` decides.
+    ///
+    /// By default, it returns `None`.
+    fn hit_test(&self, _position: Point) -> Option<bool> {
+        None
+    }
+
+    /// Returns the sizes the user interface supports.
+    ///
+    /// By default, it supports any size.
+    fn size_hints(&self) -> resize::SizeHints {
+        resize::SizeHints::new()
+    }
+
+    /// Returns whether `on_frame` is called on every frame of the display.
+    ///
+    /// By default, it returns `false`.
+    fn is_animating(&self) -> bool {
+        false
+    }
+
+    /// Produces a message from a frame of the display.
+    ///
+    /// It's called while `is_animating` returns `true`, and on the frame following a
+    /// `clock::request_animation_frame` command.
+    ///
+    /// By default, it returns `None`.
+    fn on_frame(&self, _frame: clock::Frame) -> Option<Self::Message> {
+        None
+    }
+
+    /// Produces a message once the view renders again after failing to, e.g. because an
+    /// external GPU was unplugged.
+    ///
+    /// By default, it returns `None`.
+    fn on_recovery(&self, _recovery: recovery::Recovery) -> Option<Self::Message> {
+        None
+    }
+}
+
+/// The settings of the view.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// The bytes of the font that will be used by default.
+    ///
+    /// If `None` is provided, a default system font will be chosen.
+    pub default_font: Option<&'static [u8]>,
+    /// The default size of text.
+    ///
+    /// By default, it will be set to 20.
+    pub default_text_size: u16,
+    /// The number of samples per pixel the renderer uses to antialias some primitives.
+    ///
+    /// It must be 1, 2, 4, 8 or 16. More samples produce a smoother result in some widgets, like
+    /// the `Canvas`, at a performance cost.
+    ///
+    /// By default, it is 1, which disables antialiasing.
+    pub sample_count: u32,
+    /// Whether the renderer antialiases some primitives with 4 samples per pixel.
+    ///
+    /// It's only used while `sample_count` is 1. By default, it's `false`.
+    #[deprecated(note = "use `sample_count` instead")]
+    pub antialiasing: bool,
+    /// Which GPU the view prefers.
+    ///
+    /// By default, the system decides.
+    pub power_preference: gpu::PowerPreference,
+    /// The graphics API the view renders with.
+    ///
+    /// By default, the best one available.
+    pub backend: gpu::Backend,
+    /// When rendered frames are shown.
+    ///
+    /// By default, on the next vertical blank, replacing a waiting frame.
+    pub present_mode: gpu::PresentMode,
+    /// The pixel format of the surface of the view.
+    ///
+    /// By default, 8 bits per channel in the sRGB color space.
+    pub format: gpu::Format,
+    /// The kinds of content which can be dragged into the view.
+    ///
+    /// By default, only files are accepted.
+    pub drop_types: Vec<clipboard::Kind>,
+    /// When the view receives mouse moves and enter/exit events.
+    ///
+    /// By default, only while its window is the key window.
+    pub tracking: tracking::Activation,
+    /// Whether the click activating the window of the view is also handled by the view.
+    ///
+    /// By default, it only activates the window.
+    pub first_mouse: tracking::FirstMouse,
+    /// How the view decides whether a click belongs to it or to the views underneath.
+    ///
+    /// By default, every click within its bounds belongs to it.
+    pub hit_testing: hit_test::Mode,
+    /// The zoom of the user interface, applied on top of the scale factor of the display.
+    ///
+    /// By default, it is `1.0`.
+    pub zoom: f64,
+    /// How a user interface with a reference size fills the space left around it.
+    ///
+    /// By default, it's centered and the remaining space is filled with the background color.
+    pub fit: Fit,
+    /// How the size of the view is decided.
+    ///
+    /// By default, the host decides it.
+    pub sizing: measure::Sizing,
+    /// Whether the view shares its GPU device and the caches of its renderer with the other
+    /// views of the process which share theirs.
+    ///
+    /// Only views with the same font, text size, sample count, power preference, backend and
+    /// format share them. By default, every view has its own.
+    pub share_context: bool,
+    /// The highest rate at which animation frames are delivered, in frames per second.
+    ///
+    /// Frames rendered in response to events aren't limited. By default, animations follow the
+    /// refresh rate of the display.
+    pub max_frame_rate: Option<u32>,
+    /// Which pointer events are merged when they arrive faster than frames.
+    ///
+    /// By default, cursor moves and scrolls are merged.
+    pub coalescing: coalesce::Coalescing,
+    /// Whether the view renders on a thread of its own instead of the main thread.
+    ///
+    /// The render thread has its own GPU device and renderer, in addition to the ones the main
+    /// thread uses to lay the widgets out, which may be shared with other views, so it costs
+    /// one more device per view. By default, the view renders on the main thread.
+    pub render_thread: bool,
+}
+
+impl Default for Settings {
+    #[allow(deprecated)]
+    fn default() -> Self {
+        Self {
+            default_font: Some(include_bytes!("../fonts/OpenSans-Regular.ttf")),
+            default_text_size: 20,
+            sample_count: 1,
+            antialiasing: false,
+            power_preference: gpu::PowerPreference::default(),
+            backend: gpu::Backend::default(),
+            present_mode: gpu::PresentMode::default(),
+            format: gpu::Format::default(),
+            drop_types: vec![clipboard::Kind::FileUrls],
+            tracking: tracking::Activation::default(),
+            first_mouse: tracking::FirstMouse::default(),
+            hit_testing: hit_test::Mode::default(),
+            zoom: 1.0,
+            fit: Fit::default(),
+            sizing: measure::Sizing::default(),
+            share_context: false,
+            max_frame_rate: None,
+            coalescing: coalesce::Coalescing::default(),
+            render_thread: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Checks the settings, without creating anything.
+    ///
+    /// `IcedView::try_new` returns the same error for invalid settings.
+    ///
+    /// The GPU and the surface of the view may still refuse the backend, the format or the
+    /// present mode, which is reported once the GPU device is created.
+    pub fn validate(&self) -> Result<(), Error> {
+        gpu::antialiasing(self.samples())?;
+
+        Ok(())
+    }
+
+    /// Returns the number of samples per pixel, with the deprecated `antialiasing` taken into
+    /// account.
+    #[allow(deprecated)]
+    pub(crate) fn samples(&self) -> u32 {
+        if self.antialiasing && self.sample_count == 1 {
+            4
+        } else {
+            self.sample_count
+        }
+    }
+}
+
+impl From<Settings> for RendererSettings {
+    fn from(settings: Settings) -> RendererSettings {
+        Self {
+            format: settings.format.into(),
+            default_font: settings.default_font,
+            default_text_size: settings.default_text_size,
+            // Invalid sample counts are rejected before the renderer is created.
+            antialiasing: gpu::antialiasing(settings.samples()).unwrap_or(None),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+struct Program<A: Application> {
+    // Borrowed mutably from a shared reference to measure the content.
+    application: RefCell<A>,
+    actions: RefCell<Vec<command::Action<A::Message>>>,
+    // Whether the application handled a message since the last frame.
+    is_updated: Cell<bool>,
+    fit: Fit,
+    // Whether the application panicked before it showed its interface, which is left blank.
+    is_poisoned: bool,
+}
+
+#[cfg(target_os = "macos")]
+impl<A: Application> Program<A> {
+    fn new(application: A, fit: Fit) -> Self {
+        Self {
+            application: RefCell::new(application),
+            actions: RefCell::new(Vec::new()),
+            is_updated: Cell::new(false),
+            fit,
+            is_poisoned: false,
+        }
+    }
+
+    fn application(&self) -> std::cell::Ref<'_, A> {
+        self.application.borrow()
+    }
+
+    fn take_actions(&self) -> Vec<command::Action<A::Message>> {
+        self.actions.replace(Vec::new())
+    }
+
+    fn take_is_updated(&self) -> bool {
+        self.is_updated.replace(false)
+    }
+
+    /// Returns `true` if an interactive widget of the interface lies under the point.
+    fn is_interactive(
+        &self,
+        cache: &mut hit_test::Cache,
+        renderer: &mut Renderer,
+        bounds: Size,
+        point: Point,
+    ) -> bool {
+        let mut application = self.application.borrow_mut();
+        let application = &mut *application;
+        let fit = self.fit;
+
+        cache.is_interactive(
+            move || Self::element(application, fit),
+            bounds,
+            renderer,
+            point,
+        )
+    }
+
+    /// Returns the interface of the application, letterboxed to its reference size if needed.
+    fn element(application: &mut A, fit: Fit) -> NativeElement<'_, A::Message, Renderer> {
+        let reference_size = geometry::sanitize_reference_size(application.reference_size());
+        let element = application.view();
+
+        match (reference_size, fit) {
+            (Some(size), Fit::Letterbox) => {
+                let content = Container::new(element)
+                    .width(Length::Units(size.width.round() as u16))
+                    .height(Length::Units(size.height.round() as u16));
+
+                Container::new(content)
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .center_x()
+                    .center_y()
+                    .into()
+            }
+            _ => element,
+        }
+    }
+
+    /// Returns the intrinsic size of the interface of the application.
+    fn intrinsic_size(&self, renderer: &Renderer) -> Size {
+        let mut application = self.application.borrow_mut();
+        let element = application.view();
+
+        measure::intrinsic_size(&element, renderer)
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl<A: Application> program::Program for Program<A> {
+    type Renderer = Renderer;
+    type Message = A::Message;
+
+    fn update(&mut self, message: Self::Message) -> iced_native::Command<Self::Message> {
+        let (futures, actions) = self.application.get_mut().update(message).into_parts();
+        self.actions.get_mut().extend(actions);
+        self.is_updated.set(true);
+        futures
+    }
+
+    /// Application interface.
+    fn view(&mut self) -> NativeElement<'_, Self::Message, Self::Renderer> {
+        if self.is_poisoned {
+            return Space::new(Length::Fill, Length::Fill).into();
+        }
+
+        Self::element(self.application.get_mut(), self.fit)
+    }
+}
+
+#[cfg(target_os = "macos")]
+struct EventHandler<A: 'static + Application> {
+    view: *mut Object,
+    state: program::State<Program<A>>,
+    geometry: Geometry,
+    viewport: Viewport,
+    layer: Layer,
+    surface: wgpu::Surface,
+    context: Rc<RefCell<Context>>,
+    format: wgpu::TextureFormat,
+    present_mode: wgpu::PresentMode,
+    // There's no swap chain while the view covers no pixel.
+    swap_chain: Option<wgpu::SwapChain>,
+    // The generation of the context the swap chain was created with.
+    generation: u64,
+    recovery_policy: recovery::Policy,
+    debug: Debug,
+    pasteboard: Pasteboard,
+    drag_source: drag::Source<A::Message>,
+    drop_types: Vec<clipboard::Kind>,
+    drop_destination: drop::Destination,
+    activation: tracking::Activation,
+    first_mouse: tracking::FirstMouse,
+    hit_testing: hit_test::Mode,
+    hit_test_cache: hit_test::Cache,
+    // The last event which can initiate a drag session, retained.
+    mouse_event: id,
+    resize_handler: Option<Box<dyn FnMut(Size)>>,
+    sizing: measure::Sizing,
+    // The intrinsic size of the content, in the coordinate system of the widgets.
+    intrinsic_size: Size,
+    poison: poison::Poison,
+    redraw_tracker: redraw::Tracker,
+    clock: clock::Clock<clock::System>,
+    // `None` if the system couldn't create one, in which case nothing animates.
+    display_link: Option<clock::DisplayLink>,
+    coalescer: coalesce::Coalescer,
+    // Renders instead of the main thread, which then has no swap chain.
+    render_thread: Option<render_thread::RenderThread>,
+    // The device being recreated after a failure, cancelled if the view goes away first.
+    device_request: Option<init::Request>,
+}
+
+#[cfg(target_os = "macos")]
+impl<A: 'static + Application> process::Queue for EventHandler<A> {
+    fn coalescer(&mut self) -> &mut coalesce::Coalescer {
+        &mut self.coalescer
+    }
+
+    fn deliver_event(&mut self, event: Event) {
+        self.state.queue_event(event);
+    }
+
+    fn is_queue_empty(&self) -> bool {
+        self.state.is_queue_empty()
+    }
+
+    fn update(&mut self) {
+        self.state.update(
+            Some(&self.pasteboard),
+            self.geometry.logical_size(),
+            &mut self.context.borrow_mut().renderer,
+            &mut self.debug,
+        );
+        self.redraw_tracker.invalidate();
+        self.hit_test_cache.invalidate();
+        if self.state.program().take_is_updated() {
+            self.redraw_tracker.request();
+        }
+        self.perform_actions();
+        // Messages may change the reference size, which resizes the user interface.
+        self.resize_to_reference_size();
+    }
+}
+
+/// What a view holds while its GPU device is created.
+#[cfg(target_os = "macos")]
+struct Loading<A: Application> {
+    application: A,
+    settings: Settings,
+    geometry: Geometry,
+    layer: Layer,
+    // The context the view shares, kept alive until the view starts.
+    shared: Option<Rc<RefCell<Context>>>,
+    resize_handler: Option<Box<dyn FnMut(Size)>>,
+    init_handler: Option<Box<dyn FnOnce(Result<(), Error>)>>,
+    // Dropping it cancels the initialization, e.g. when the view is deallocated first.
+    _request: Option<init::Request>,
+}
+
+#[cfg(target_os = "macos")]
+impl<A: Application> Loading<A> {
+    /// Returns the context of the main thread and the render thread, if the settings ask for
+    /// one, from the contexts created in the background.
+    ///
+    /// The render thread calls `wake` on the main thread when it recovers from a failure.
+    fn take_contexts(
+        &mut self,
+        contexts: context::Contexts,
+        wake: impl Fn() + Send + Sync + 'static,
+    ) -> Result<(Rc<RefCell<Context>>, Option<render_thread::RenderThread>), Error> {
+        let context::Contexts { main, render } = contexts;
+        let context = match (main, self.shared.take()) {
+            (Some(context), _) => context.into_shared(&self.settings),
+            (None, Some(shared)) => shared,
+            // The provider only skips the context the view shares.
+            (None, None) => return Err(Error::DeviceRequestFailed),
+        };
+        let render_thread = match render {
+            Some((surface, context)) => Some(render_thread::RenderThread::spawn(
+                self.layer.0,
+                surface,
+                context,
+                self.settings.format.into(),
+                self.settings.present_mode.into(),
+                wake,
+            )?),
+            None => None,
+        };
+
+        Ok((context, render_thread))
+    }
+}
+
+/// Creates the Cocoa objects of the views of an application type.
+#[cfg(target_os = "macos")]
+struct Cocoa<'a, A> {
+    drop_types: &'a [clipboard::Kind],
+    _phantom_app: PhantomData<A>,
+}
+
+#[cfg(target_os = "macos")]
+impl<A: 'static + Application> platform::Platform for Cocoa<'_, A> {
+    type View = *mut Object;
+    type Layer = Layer;
+
+    fn create_view(&mut self, size: Size) -> Result<*mut Object, Error> {
+        unsafe { IcedView::<A>::init_nsview(size, self.drop_types) }
+    }
+
+    fn create_layer(&mut self, view: &*mut Object, scale_factor: f64) -> Result<Layer, Error> {
+        unsafe { Layer::new(*view, scale_factor) }
+    }
+
+    fn release_view(&mut self, view: *mut Object) {
+        unsafe {
+            let () = msg_send![view, release];
+        }
+    }
+}
+
+/// The `CAMetalLayer` the view renders to, released when it's dropped.
+#[cfg(target_os = "macos")]
+struct Layer(*mut Object);
+
+#[cfg(target_os = "macos")]
+impl Layer {
+    /// Adds a layer to the layer of the view.
+    unsafe fn new(view: *mut Object, scale: f64) -> Result<Self, Error> {
+        let class = Class::get("CAMetalLayer").ok_or(Error::SurfaceCreationFailed)?;
+        let layer: *mut Object = msg_send![class, new];
+        if layer.is_null() {
+            return Err(Error::SurfaceCreationFailed);
+        }
+        let () = msg_send![view, setWantsLayer: YES];
+        let parent: *mut Object = msg_send![view, layer];
+        let () = msg_send![parent, addSublayer: layer];
+        let bounds: CGRect = msg_send![view, bounds];
+        let () = msg_send![layer, setBounds: bounds];
+        let () = msg_send![layer, setContentsScale: scale];
+        let () = msg_send![layer, setAnchorPoint: CGPoint::new(0.0, 0.0)];
+        // kCALayerWidthSizable | kCALayerHeightSizable
+        let autoresizing_mask = 1u64 << 1 | 1 << 4;
+        let () = msg_send![layer, setAutoresizingMask: autoresizing_mask];
+
+        Ok(Self(layer))
+    }
+
+    /// Fills the layer with a color, which shows until a frame is presented.
+    fn set_background_color(&self, color: Color) {
+        unsafe {
+            let color: id = msg_send![class!(NSColor),
+                colorWithSRGBRed: CGFloat::from(color.r)
+                green: CGFloat::from(color.g)
+                blue: CGFloat::from(color.b)
+                alpha: CGFloat::from(color.a)];
+            let color: *mut c_void = msg_send![color, CGColor];
+            let () = msg_send![self.0, setBackgroundColor: color];
+        }
+    }
+
+    fn set_contents_scale(&self, scale: f64) {
+        unsafe {
+            let () = msg_send![self.0, setContentsScale: scale];
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for Layer {
+    fn drop(&mut self) {
+        // The layer of the view keeps its sublayer alive until the view is deallocated.
+        unsafe {
+            let () = msg_send![self.0, release];
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl<A: 'static + Application> EventHandler<A> {
+    fn new(
+        application: A,
+        object: *mut Object,
+        geometry: Geometry,
+        settings: Settings,
+        layer: Layer,
+        context: Rc<RefCell<Context>>,
+        render_thread: Option<render_thread::RenderThread>,
+    ) -> Self {
+        let surface =
+            wgpu::Surface::create_surface_from_core_animation_layer(layer.0 as *mut c_void);
+        let drop_types = settings.drop_types.clone();
+        let activation = settings.tracking;
+        let first_mouse = settings.first_mouse;
+        let hit_testing = settings.hit_testing;
+        let fit = settings.fit;
+        let sizing = settings.sizing;
+        let max_frame_rate = settings.max_frame_rate;
+        let coalescing = settings.coalescing;
+        let format = settings.format.into();
+        let present_mode = settings.present_mode.into();
+        let swap_chain = if render_thread.is_some() {
+            None
+        } else {
+            context
+                .borrow()
+                .create_swap_chain(&surface, &geometry, format, present_mode)
+        };
+        let generation = context.borrow().generation();
+        let mut debug = Debug::new();
+        let mut program = Program::new(application, fit);
+        let mut poison = poison::Poison::new();
+        // The application shows its interface for the first time. If it panics, the view starts
+        // poisoned, with a blank interface, and keeps the application until it's recovered.
+        let measured = poison::catch(|| {
+            let reference_size = program.application().reference_size();
+            let intrinsic_size = program.intrinsic_size(&context.borrow().renderer);
+
+            (reference_size, intrinsic_size)
+        });
+        let (reference_size, intrinsic_size) = match measured {
+            Ok(measured) => measured,
+            Err(panic) => {
+                program.is_poisoned = true;
+                poison.poison(panic);
+
+                (None, Size::ZERO)
+            }
+        };
+        let geometry = geometry.with_reference_size(reference_size);
+        let state = program::State::new(
+            program,
+            geometry.logical_size(),
+            &mut context.borrow_mut().renderer,
+            &mut debug,
+        );
+
+        Self {
+            view: object,
+            state,
+            geometry,
+            viewport: geometry.viewport(),
+            layer,
+            surface,
+            context,
+            format,
+            present_mode,
+            swap_chain,
+            generation,
+            recovery_policy: recovery::Policy::new(),
+            debug,
+            pasteboard: Pasteboard::new(),
+            drag_source: drag::Source::new(),
+            drop_types,
+            drop_destination: drop::Destination::new(),
+            activation,
+            first_mouse,
+            hit_testing,
+            hit_test_cache: hit_test::Cache::new(),
+            mouse_event: nil,
+            resize_handler: None,
+            sizing,
+            intrinsic_size,
+            poison,
+            redraw_tracker: redraw::Tracker::new(),
+            clock: clock::Clock::new(max_frame_rate),
+            display_link: clock::DisplayLink::new(object, IcedView::<A>::on_display_link),
+            coalescer: coalesce::Coalescer::new(coalescing),
+            render_thread,
+            device_request: None,
+        }
+    }
+
+    fn resize(&mut self, geometry: Geometry) {
+        if geometry == self.geometry {
+            return;
+        }
+
+        let is_resized = geometry.physical_size() != self.geometry.physical_size();
+        if geometry.scale_factor() != self.geometry.scale_factor() {
+            self.layer.set_contents_scale(geometry.scale_factor());
+        }
+
+        self.geometry = geometry;
+        self.viewport = geometry.viewport();
+        // The render thread resizes its own swap chain.
+        if is_resized && self.render_thread.is_none() {
+            self.recreate_swap_chain();
+        }
+
+        let size = geometry.logical_size();
+        self.on_window_event(window::Event::Resized {
+            width: size.width.round() as u32,
+            height: size.height.round() as u32,
+        });
+    }
+
+    fn on_window_event(&mut self, event: window::Event) {
+        self.queue_event(vec![Event::Window(event)]);
+    }
+
+    fn queue_event(&mut self, events: Vec<Event>) {
+        process::push(self, events);
+    }
+
+    fn redraw(&mut self) {
+        self.collect_reports();
+        // A panic of the render thread stops the user interface.
+        if self.poison.is_poisoned() {
+            return;
+        }
+        process::flush(self);
+        self.update_state();
+
+        self.set_cursor_icon(self.state.primitive().1);
+
+        let presented = self.next_frame();
+        if !self.redraw_tracker.should_redraw(&presented) {
+            return;
+        }
+
+        if let Some(render_thread) = &self.render_thread {
+            render_thread.submit(render_thread::Job {
+                primitive: self.state.primitive().0.clone(),
+                background_color: presented.background_color,
+                geometry: self.geometry,
+            });
+            self.redraw_tracker.presented(presented);
+            return;
+        }
+
+        let frame = match self.next_texture() {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        self.debug.render_started();
+
+        let overlay = self.debug.overlay();
+        let context = &self.context;
+        let viewport = &self.viewport;
+        let primitive = self.state.primitive();
+        // wgpu panics when the device fails while drawing.
+        let drawn = poison::catch(|| {
+            context.borrow_mut().draw(
+                &frame,
+                viewport,
+                presented.background_color,
+                primitive,
+                &overlay,
+            )
+        });
+
+        self.debug.render_finished();
+
+        match drawn {
+            Ok(()) => {
+                self.redraw_tracker.presented(presented);
+                self.on_render_success();
+            }
+            Err(panic) => match self.diagnose_panic() {
+                Some(failure) => self.on_render_failure(failure),
+                None => self.poison_view(panic),
+            },
+        }
+    }
+
+    /// Returns the texture to render the next frame into.
+    ///
+    /// Returns `None` if there's no swap chain, if the swap chain didn't provide a texture, in
+    /// which case the failure is handled, or while the next attempt has to wait.
+    fn next_texture(&mut self) -> Option<wgpu::SwapChainOutput> {
+        // Frames wait for the device being recreated.
+        if self.device_request.is_some() || !self.recovery_policy.is_due(self.clock.now()) {
+            return None;
+        }
+        // Another view sharing the context may have recreated its device.
+        if self.generation != self.context.borrow().generation() {
+            self.recreate_swap_chain();
+        }
+
+        match self.swap_chain.as_mut()?.get_next_texture() {
+            Ok(frame) => Some(frame),
+            Err(_) => {
+                let size = self.geometry.physical_size();
+                let failure = unsafe { recovery::diagnose(self.layer.0, size) };
+                self.on_render_failure(failure);
+                None
+            }
+        }
+    }
+
+    fn recreate_swap_chain(&mut self) {
+        let context = self.context.borrow();
+        self.swap_chain = context.create_swap_chain(
+            &self.surface,
+            &self.geometry,
+            self.format,
+            self.present_mode,
+        );
+        self.generation = context.generation();
+    }
+
+    /// Responds to a frame which couldn't be rendered. The display link wakes the view up for
+    /// the next attempt.
+    fn on_render_failure(&mut self, failure: recovery::Failure) {
+        match self.recovery_policy.on_failure(failure, self.clock.now()) {
+            Some(recovery::Action::Retry) => {}
+            Some(recovery::Action::RecreateSwapChain) => self.recreate_swap_chain(),
+            Some(recovery::Action::RecreateDevice) => self.request_device(),
+            // The next change of the user interface tries again.
+            None => {}
+        }
 
-            let value = this.get_mut_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
-            let event_handler = *value as *mut EventHandler<A>;
+        self.schedule_frames();
+    }
 
-            Self::paths_from_dragged_info(sender)
-                .into_iter()
-                .for_each(|pathbuf| {
-                    (*event_handler).on_window_event(window::Event::FileHovered(pathbuf));
-                });
+    /// Creates a new device on a background thread, like the first one, unless it's already
+    /// being created.
+    fn request_device(&mut self) {
+        if self.device_request.is_some() {
+            return;
         }
-        // NSDragOperationEvery
-        NSUInteger::MAX
+
+        let settings = Settings {
+            render_thread: false,
+            ..self.context.borrow().settings().clone()
+        };
+        let provider = context::Provider::new(self.layer.0, settings, false);
+        let view = self.view as usize;
+        self.device_request = Some(init::spawn(
+            provider,
+            init::main_queue,
+            move |result| unsafe { IcedView::<A>::on_device_created(view as *mut Object, result) },
+        ));
     }
 
-    extern "C" fn dragging_ended(this: &mut Object, _cmd: Sel, sender: *mut Object) {
-        unsafe {
-            if *this.get_ivar::<bool>(Self::DID_EXIT_DRAG) {
-                return;
-            }
-            let value = this.get_mut_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
-            let event_handler = *value as *mut EventHandler<A>;
+    /// Replaces the device with the one created in the background. On error, the next failure
+    /// tries again.
+    fn on_device_created(&mut self, result: Result<context::Contexts, Error>) {
+        self.device_request = None;
+        if let Ok(context::Contexts {
+            main: Some(context),
+            ..
+        }) = result
+        {
+            self.context.borrow_mut().replace(context);
+            self.recreate_swap_chain();
+        }
 
-            Self::paths_from_dragged_info(sender)
-                .into_iter()
-                .for_each(|pathbuf| {
-                    (*event_handler).on_window_event(window::Event::FileDropped(pathbuf));
-                });
+        unsafe {
+            let () = msg_send![self.view, setNeedsDisplay: YES];
         }
     }
 
-    unsafe fn paths_from_dragged_info(info: *mut Object) -> Vec<PathBuf> {
-        let pasteboard: id = msg_send![info, draggingPasteboard];
-        let class = class!(NSURL);
-        let class_ref: *mut Object = msg_send![class, self];
-        let classes = NSArray::arrayWithObject(nil, class_ref);
-        let items: id = msg_send![pasteboard, readObjectsForClasses: classes options: nil];
-        (0..items.count())
-            .into_iter()
-            .map(|n| pathbuf_from_nsurl(items.objectAtIndex(n)))
-            .collect()
+    /// Returns the failure behind a panic while drawing, or `None` if the GPU isn't to blame.
+    fn diagnose_panic(&self) -> Option<recovery::Failure> {
+        let size = self.geometry.physical_size();
+
+        recovery::panic_failure(unsafe { recovery::diagnose(self.layer.0, size) })
     }
 
-    extern "C" fn dragging_exited(this: &mut Object, _cmd: Sel, _sender: *mut Object) {
+    /// Poisons the view with a panic it caught itself, and shows it.
+    fn poison_view(&mut self, panic: poison::Panic) {
+        self.poison.poison(panic);
         unsafe {
-            this.set_ivar::<bool>(Self::DID_EXIT_DRAG, true);
-            let value = this.get_mut_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
-            let event_handler = *value as *mut EventHandler<A>;
+            let () = msg_send![self.view, setNeedsDisplay: YES];
+        }
+    }
 
-            (*event_handler).on_window_event(window::Event::FilesHoveredLeft);
+    fn on_render_success(&mut self) {
+        if let Some(recovery) = self.recovery_policy.on_success() {
+            self.on_recovery(recovery);
         }
     }
 
-    extern "C" fn handle_event(this: &mut Object, _cmd: Sel, event: *mut Object) {
-        unsafe {
-            let value = this.get_mut_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
-            let event_handler = *value as *mut EventHandler<A>;
-            (*event_handler).queue_event(
-                NSEventT {
-                    raw_event: event,
-                    view: this,
-                }
-                .into(),
-            );
-            let () = msg_send![this, setNeedsDisplay: YES];
+    /// Handles what the render thread reported.
+    fn collect_reports(&mut self) {
+        let reports = match &self.render_thread {
+            Some(render_thread) => render_thread.take_reports(),
+            None => return,
         };
+
+        for report in reports {
+            match report {
+                render_thread::Report::Recovery(recovery) => self.on_recovery(recovery),
+                render_thread::Report::Panic(panic) => self.poison_view(panic),
+            }
+        }
     }
 
-    /// Get a raw pointer to the Cocoa view.
-    pub fn raw_object(&self) -> *mut Object {
-        self.object
+    fn on_recovery(&mut self, recovery: recovery::Recovery) {
+        let message = self.state.program().application().on_recovery(recovery);
+        if let Some(message) = message {
+            self.state.queue_message(message);
+            // It's handled on the next tick of the display link.
+            self.schedule_frames();
+        }
     }
 
-    /// Make this view a subview of another view.
-    pub unsafe fn make_subview_of(&self, view: *mut c_void) {
-        NSView::addSubview_(view as id, self.object);
+    /// Returns what the next frame shows.
+    fn next_frame(&mut self) -> redraw::Frame {
+        let background_color = self.state.program().application().background_color();
+
+        self.redraw_tracker
+            .next_frame(&self.state.primitive().0, background_color, self.geometry)
     }
-}
 
-impl<A: 'static + Application> Drop for IcedView<A> {
-    fn drop(&mut self) {
-        unsafe {
-            let value = self
-                .object
-                .as_mut()
-                .unwrap()
-                .get_mut_ivar::<*mut c_void>(Self::EVENT_HANDLER_IVAR);
-            let _ = Box::from_raw(*value as *mut EventHandler<A>);
-            let () = msg_send![self.object, release];
+    /// Draws the panic which poisoned the view instead of the user interface.
+    fn redraw_poisoned(&mut self) {
+        let content = match self.poison.panic() {
+            Some(panic) => format!("The interface stopped because of an error.\n\n{}", panic),
+            None => return,
+        };
+
+        let size = self.geometry.logical_size();
+        let padding = 16.0;
+        let primitive = Primitive::Text {
+            content,
+            bounds: Rectangle {
+                x: padding,
+                y: padding,
+                width: (size.width - padding * 2.0).max(0.0),
+                height: (size.height - padding * 2.0).max(0.0),
+            },
+            color: Color::WHITE,
+            size: 16.0,
+            font: Font::Default,
+            horizontal_alignment: HorizontalAlignment::Left,
+            vertical_alignment: VerticalAlignment::Top,
+        };
+        let background_color = Color::from_rgb(0.2, 0.2, 0.2);
+
+        self.set_cursor_icon(mouse::Interaction::Idle);
+        // The layer doesn't show the user interface anymore.
+        self.redraw_tracker.request();
+
+        if let Some(render_thread) = &self.render_thread {
+            render_thread.submit(render_thread::Job {
+                primitive,
+                background_color,
+                geometry: self.geometry,
+            });
+            return;
         }
-    }
-}
 
-/// Implement this trait for your application then pass it into `IcedView::new`.
-pub trait Application {
-    /// The message your application will produce.
-    type Message: Clone + std::fmt::Debug + Send;
+        let frame = match self.next_texture() {
+            Some(frame) => frame,
+            None => return,
+        };
 
-    /// Message processing function.
-    fn update(&mut self, message: Self::Message) -> Command<Self::Message>;
+        let context = &self.context;
+        let viewport = &self.viewport;
+        let drawn = poison::catch(|| {
+            context.borrow_mut().draw(
+                &frame,
+                viewport,
+                background_color,
+                &(primitive, mouse::Interaction::Idle),
+                &[] as &[String],
+            )
+        });
 
-    /// Application interface.
-    fn view(&mut self) -> Element<'_, Self::Message>;
+        match drawn {
+            Ok(()) => self.on_render_success(),
+            // Otherwise, the error screen itself can't be drawn.
+            Err(_) => {
+                if let Some(failure) = self.diagnose_panic() {
+                    self.on_render_failure(failure);
+                }
+            }
+        }
+    }
 
-    /// Returns the background color of the [`Application`].
-    ///
-    /// By default, it returns `Color::WHITE`.
-    fn background_color(&self) -> Color {
-        Color::WHITE
+    /// Replaces the application, so a poisoned view shows the user interface again.
+    fn recover(&mut self, application: A) -> Option<poison::Panic> {
+        let fit = self.state.program().fit;
+        let program = Program::new(application, fit);
+        self.state = program::State::new(
+            program,
+            self.geometry.logical_size(),
+            &mut self.context.borrow_mut().renderer,
+            &mut self.debug,
+        );
+        self.redraw_tracker.invalidate();
+        self.hit_test_cache.invalidate();
+        self.drop_destination.end();
+        self.intrinsic_size = self
+            .state
+            .program()
+            .intrinsic_size(&self.context.borrow().renderer);
+
+        self.poison.recover()
     }
-}
 
-/// The settings of the view.
-#[derive(Debug)]
-pub struct Settings {
-    /// The bytes of the font that will be used by default.
-    ///
-    /// If `None` is provided, a default system font will be chosen.
-    pub default_font: Option<&'static [u8]>,
-    /// The default size of text.
-    ///
-    /// By default, it will be set to 20.
-    pub default_text_size: u16,
-    /// If set to true, the renderer will try to perform antialiasing for some primitives.
+    /// Handles the queued events and messages right away, and schedules a frame if the user
+    /// interface changed.
     ///
-    /// Enabling it can produce a smoother result in some widgets, like the `Canvas`, at a
-    /// performance cost.
-    ///
-    /// By default, it is disabled.
-    pub antialiasing: bool,
-}
+    /// Every callback of AppKit queuing events processes them before returning, so the
+    /// application sees them in the order AppKit delivered them, whenever the view renders.
+    /// Only the pointer events merged by the coalescer wait for the next frame. Frames show the
+    /// state after the last processed event.
+    fn process_queue(&mut self) {
+        self.collect_reports();
+        // A panic of the render thread stops the user interface.
+        if self.poison.is_poisoned() {
+            return;
+        }
+        self.update_state();
+        self.set_cursor_icon(self.state.primitive().1);
 
-impl Default for Settings {
-    fn default() -> Self {
-        Self {
-            default_font: Some(include_bytes!("../fonts/OpenSans-Regular.ttf")),
-            default_text_size: 20,
-            antialiasing: false,
+        // Events held by the coalescer are handled on the next frame.
+        let frame = self.next_frame();
+        if !self.coalescer.is_empty() || self.redraw_tracker.should_redraw(&frame) {
+            unsafe {
+                let () = msg_send![self.view, setNeedsDisplay: YES];
+            }
         }
     }
-}
 
-impl From<Settings> for RendererSettings {
-    fn from(settings: Settings) -> RendererSettings {
-        Self {
-            default_font: settings.default_font,
-            default_text_size: settings.default_text_size,
-            antialiasing: if settings.antialiasing {
-                Some(settings::Antialiasing::MSAAx4)
-            } else {
-                None
-            },
-            ..Default::default()
+    fn update_state(&mut self) {
+        self.resize_to_reference_size();
+        if process::process(self) {
+            self.update_intrinsic_size();
         }
+        self.schedule_frames();
     }
-}
 
-struct Program<A: Application> {
-    application: A,
-}
+    fn resize_to_reference_size(&mut self) {
+        let reference_size = self.state.program().application().reference_size();
+        self.resize(self.geometry.with_reference_size(reference_size));
+    }
 
-impl<A: Application> Program<A> {
-    fn new(application: A) -> Self {
-        Self { application }
+    /// Starts the display link while the clock needs it, and stops it otherwise.
+    fn schedule_frames(&mut self) {
+        let is_animating = self.state.program().application().is_animating();
+        // Failed frames are tried again, and messages reporting recoveries handled, on ticks.
+        let is_running = self.clock.is_running(is_animating)
+            || self.recovery_policy.is_recovering()
+            || !self.state.is_queue_empty();
+
+        if let Some(display_link) = &mut self.display_link {
+            if is_running {
+                display_link.start();
+            } else {
+                display_link.stop();
+            }
+        }
     }
-}
 
-impl<A: Application> program::Program for Program<A> {
-    type Renderer = Renderer;
-    type Message = A::Message;
+    /// Delivers a frame to the application if one is due, and returns `true` if the view has
+    /// work to process: a frame, messages, or a failed frame to try again.
+    fn on_display_frame(&mut self) -> bool {
+        let is_animating = self.state.program().application().is_animating();
+        let frame = self.clock.tick(is_animating);
 
-    fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
-        self.application.update(message)
-    }
+        if let Some(frame) = frame {
+            let message = self.state.program().application().on_frame(frame);
+            if let Some(message) = message {
+                self.state.queue_message(message);
+            }
+        }
+        let is_retry_due =
+            self.recovery_policy.is_recovering() && self.recovery_policy.is_due(self.clock.now());
+        self.schedule_frames();
 
-    /// Application interface.
-    fn view(&mut self) -> NativeElement<'_, Self::Message, Self::Renderer> {
-        self.application.view()
+        frame.is_some() || is_retry_due || !self.state.is_queue_empty()
     }
-}
 
-struct EventHandler<A: 'static + Application> {
-    state: program::State<Program<A>>,
-    viewport: Viewport,
-    surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    format: wgpu::TextureFormat,
-    swap_chain: wgpu::SwapChain,
-    debug: Debug,
-    renderer: Renderer,
-    pasteboard: Pasteboard,
-}
+    fn set_display(&mut self, display: clock::CGDirectDisplayID) {
+        if let Some(display_link) = &mut self.display_link {
+            display_link.set_display(display);
+        }
+    }
 
-impl<A: 'static + Application> EventHandler<A> {
-    fn new(application: A, object: *mut Object, viewport: Viewport, settings: Settings) -> Self {
-        let surface = unsafe { Self::init_surface_layer(object, viewport.scale_factor()) };
-        let (mut device, queue) = Self::init_device_and_queue(&surface);
-        let format = wgpu::TextureFormat::Bgra8UnormSrgb;
-        let swap_chain =
-            Self::init_swap_chain(&viewport.physical_size(), &device, &surface, &format);
-        let mut debug = Debug::new();
-        let mut renderer = Renderer::new(Backend::new(&mut device, settings.into()));
-        let program = Program::new(application);
-        let state: program::State<Program<A>> =
-            program::State::new(program, viewport.logical_size(), &mut renderer, &mut debug);
+    /// Measures the content again, and notifies the host if its intrinsic size changed.
+    fn update_intrinsic_size(&mut self) {
+        let intrinsic_size = self
+            .state
+            .program()
+            .intrinsic_size(&self.context.borrow().renderer);
+        if intrinsic_size == self.intrinsic_size {
+            return;
+        }
 
-        Self {
-            state,
-            viewport,
-            surface,
-            device,
-            queue,
-            format,
-            swap_chain,
-            debug,
-            renderer,
-            pasteboard: Pasteboard::new(),
+        self.intrinsic_size = intrinsic_size;
+        unsafe {
+            let () = msg_send![self.view, invalidateIntrinsicContentSize];
         }
+        self.fit_content();
     }
 
-    unsafe fn init_surface_layer(view: *mut Object, scale: f64) -> wgpu::Surface {
-        let class = class!(CAMetalLayer);
-        let layer: *mut Object = msg_send![class, new];
-        let () = msg_send![view, setWantsLayer: YES];
-        let parent: *mut Object = msg_send![view, layer];
-        let () = msg_send![parent, addSublayer: layer];
-        let bounds: CGRect = msg_send![view, bounds];
-        let () = msg_send![layer, setBounds: bounds];
-        let () = msg_send![layer, setContentsScale: scale];
-        let () = msg_send![layer, setAnchorPoint: CGPoint::new(0.0, 0.0)];
-        // kCALayerWidthSizable | kCALayerHeightSizable
-        let autoresizing_mask = 1u64 << 1 | 1 << 4;
-        let () = msg_send![layer, setAutoresizingMask: autoresizing_mask];
-        let _: *mut c_void = msg_send![view, retain];
+    fn intrinsic_content_size(&self) -> Size {
+        let size = self.geometry.to_points(Point::new(
+            self.intrinsic_size.width,
+            self.intrinsic_size.height,
+        ));
 
-        wgpu::Surface::create_surface_from_core_animation_layer(layer as *mut c_void)
+        Size::new(size.x, size.y)
     }
 
-    fn init_device_and_queue(surface: &wgpu::Surface) -> (wgpu::Device, wgpu::Queue) {
-        futures::executor::block_on(async {
-            let adapter = wgpu::Adapter::request(
-                &wgpu::RequestAdapterOptions {
-                    power_preference: wgpu::PowerPreference::Default,
-                    compatible_surface: Some(&surface),
-                },
-                wgpu::BackendBit::PRIMARY,
-            )
-            .await
-            .expect("Request adapter");
-
-            adapter
-                .request_device(&wgpu::DeviceDescriptor {
-                    extensions: wgpu::Extensions {
-                        anisotropic_filtering: false,
-                    },
-                    limits: wgpu::Limits::default(),
-                })
-                .await
-        })
+    /// Requests the intrinsic size of the content, if the view fits it.
+    fn fit_content(&mut self) {
+        if self.sizing != measure::Sizing::FitContent {
+            return;
+        }
+
+        let current = self.geometry.logical_size();
+        let fit = |intrinsic: f32, current: f32| {
+            if intrinsic.is_finite() {
+                intrinsic
+            } else {
+                current
+            }
+        };
+
+        self.request_resize(Size::new(
+            fit(self.intrinsic_size.width, current.width),
+            fit(self.intrinsic_size.height, current.height),
+        ));
     }
 
-    fn init_swap_chain(
-        size: &Size<u32>,
-        device: &wgpu::Device,
-        surface: &wgpu::Surface,
-        format: &wgpu::TextureFormat,
-    ) -> wgpu::SwapChain {
-        device.create_swap_chain(
-            &surface,
-            &wgpu::SwapChainDescriptor {
-                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-                format: format.clone(),
-                width: size.width,
-                height: size.height,
-                present_mode: wgpu::PresentMode::Mailbox,
-            },
-        )
+    fn perform_actions(&mut self) {
+        for action in self.state.program().take_actions() {
+            let message = match action {
+                command::Action::Clipboard(action) => action.perform(&mut self.pasteboard),
+                command::Action::Drag(action) => self.begin_drag(action),
+                command::Action::Resize(size) => {
+                    self.request_resize(size);
+                    None
+                }
+                command::Action::Redraw => {
+                    self.redraw_tracker.request();
+                    None
+                }
+                command::Action::AnimationFrame => {
+                    self.clock.request();
+                    None
+                }
+            };
+
+            if let Some(message) = message {
+                self.state.queue_message(message);
+            }
+        }
     }
 
-    fn resize(&mut self, new_size: Size<u32>, scale_factor: f64) {
-        self.viewport = Viewport::with_physical_size(new_size, scale_factor);
+    fn begin_drag(&mut self, action: drag::Action<A::Message>) -> Option<A::Message> {
+        match self.drag_source.begin(action) {
+            Ok(drag) => {
+                if unsafe { drag::begin_session(self.view, self.mouse_event, &drag) } {
+                    None
+                } else {
+                    self.drag_source.fail()
+                }
+            }
+            Err(message) => Some(message),
+        }
+    }
 
-        self.swap_chain = self.device.create_swap_chain(
-            &self.surface,
-            &wgpu::SwapChainDescriptor {
-                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-                format: self.format,
-                width: new_size.width,
-                height: new_size.height,
-                present_mode: wgpu::PresentMode::Mailbox,
-            },
-        );
+    fn constrain_size(&self, proposed: Size) -> Size {
+        let hints = self.state.program().application().size_hints();
 
-        self.on_window_event(window::Event::Resized {
-            width: new_size.width,
-            height: new_size.height,
-        });
+        constrain_size(self.geometry, &hints, proposed)
     }
 
-    fn on_window_event(&mut self, event: window::Event) {
-        self.queue_event(vec![Event::Window(event)]);
+    fn request_resize(&mut self, size: Size) {
+        let hints = self.state.program().application().size_hints();
+        let size = hints.constrain(size);
+        let size = self.geometry.to_points(Point::new(size.width, size.height));
+        let size = Size::new(size.x, size.y);
+
+        if size == self.geometry.size() {
+            return;
+        }
+
+        match &mut self.resize_handler {
+            Some(resize_handler) => resize_handler(size),
+            None => unsafe { resize_view(self.view, size) },
+        }
     }
 
-    fn queue_event(&mut self, events: Vec<Event>) {
-        events.into_iter().for_each(|e| self.state.queue_event(e));
+    fn end_drag(&mut self, operation: drag::Operation) {
+        if let Some(message) = self.drag_source.end(operation) {
+            self.state.queue_message(message);
+        }
+        // The dragging session consumes the mouse up event.
+        self.queue_event(vec![Event::Mouse(mouse::Event::ButtonReleased(
+            mouse::Button::Left,
+        ))]);
     }
 
-    fn redraw(&mut self) {
-        self.update_state();
+    fn on_drag_entered(&mut self, pasteboard: Pasteboard, operations: drag::Operation) {
+        let payload = drop::Payload::read(&pasteboard, &self.drop_types, operations);
+        payload.files().iter().for_each(|path| {
+            self.on_window_event(window::Event::FileHovered(path.clone()));
+        });
+
+        let event = self.drop_destination.enter(payload);
+        self.on_drop_event(event);
+    }
 
-        if let Ok(frame) = self.swap_chain.get_next_texture() {
-            self.debug.render_started();
+    fn on_drag_updated(&mut self, position: Point) -> drag::Operation {
+        let position = self.geometry.to_logical(position);
+        let program = self.state.program();
+        let (operation, event) = self.drop_destination.update(position, |payload, position| {
+            program.application().drop_operation(payload, position)
+        });
 
-            let mut encoder = self
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        if let Some(event) = event {
+            self.on_drop_event(event);
+        }
 
-            self.render_pass(&frame, &mut encoder);
+        operation
+    }
 
-            let mouse_interaction = self.render_pass_iced(&frame, &mut encoder);
+    fn on_drag_exited(&mut self) {
+        if let Some(event) = self.drop_destination.leave() {
+            self.on_window_event(window::Event::FilesHoveredLeft);
+            self.on_drop_event(event);
+        }
+    }
 
-            self.queue.submit(&[encoder.finish()]);
+    fn on_drag_performed(&mut self) -> bool {
+        match self.drop_destination.perform() {
+            Some(event) => {
+                if let drop::Event::Dropped { payload, .. } = &event {
+                    payload.files().iter().for_each(|path| {
+                        self.on_window_event(window::Event::FileDropped(path.clone()));
+                    });
+                }
+                self.on_drop_event(event);
+                true
+            }
+            None => false,
+        }
+    }
 
-            self.debug.render_finished();
+    fn on_drag_ended(&mut self) {
+        self.drop_destination.end();
+    }
 
-            self.set_cursor_icon(mouse_interaction);
+    fn hit_test(&mut self, position: Point) -> bool {
+        let position = self.geometry.to_logical(position);
+        // The application is borrowed again to lay the widgets out.
+        let hit = self.state.program().application().hit_test(position);
+        match hit {
+            Some(hit) => hit,
+            None => match self.hit_testing {
+                hit_test::Mode::Bounds => true,
+                // Laid out like the frames, letterbox included.
+                hit_test::Mode::Widgets => self.state.program().is_interactive(
+                    &mut self.hit_test_cache,
+                    &mut self.context.borrow_mut().renderer,
+                    self.geometry.logical_size(),
+                    position,
+                ),
+            },
         }
     }
 
-    fn update_state(&mut self) {
-        if !self.state.is_queue_empty() {
-            self.state.update(
-                Some(&self.pasteboard),
-                self.viewport.logical_size(),
-                &mut self.renderer,
-                &mut self.debug,
-            );
+    fn on_drop_event(&mut self, event: drop::Event) {
+        if let Some(message) = self.state.program().application().on_drop(event) {
+            self.state.queue_message(message);
         }
     }
 
-    fn render_pass(&mut self, frame: &wgpu::SwapChainOutput, encoder: &mut wgpu::CommandEncoder) {
-        let background_color = self.state.program().application.background_color();
-
-        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                attachment: &frame.view,
-                resolve_target: None,
-                load_op: wgpu::LoadOp::Clear,
-                store_op: wgpu::StoreOp::Store,
-                clear_color: wgpu::Color {
-                    r: background_color.r as f64,
-                    g: background_color.g as f64,
-                    b: background_color.b as f64,
-                    a: background_color.a as f64,
-                },
-            }],
-            depth_stencil_attachment: None,
-        });
+    fn track_mouse_event(&mut self, event: id) {
+        unsafe {
+            match NSEvent::eventType(event) {
+                NSEventType::NSLeftMouseDown | NSEventType::NSLeftMouseDragged => {
+                    let _: id = msg_send![event, retain];
+                    self.release_mouse_event();
+                    self.mouse_event = event;
+                }
+                NSEventType::NSLeftMouseUp => self.release_mouse_event(),
+                _ => (),
+            }
+        }
     }
 
-    fn render_pass_iced(
-        &mut self,
-        frame: &wgpu::SwapChainOutput,
-        encoder: &mut wgpu::CommandEncoder,
-    ) -> mouse::Interaction {
-        self.renderer.backend_mut().draw(
-            &mut self.device,
-            encoder,
-            &frame.view,
-            &self.viewport,
-            self.state.primitive(),
-            &self.debug.overlay(),
-        )
+    fn release_mouse_event(&mut self) {
+        if !self.mouse_event.is_null() {
+            unsafe {
+                let () = msg_send![self.mouse_event, release];
+            }
+            self.mouse_event = nil;
+        }
     }
 
     fn set_cursor_icon(&self, cursor: mouse::Interaction) {
@@ -605,20 +2465,36 @@ impl<A: 'static + Application> EventHandler<A> {
     }
 }
 
+#[cfg(target_os = "macos")]
+impl<A: 'static + Application> Drop for EventHandler<A> {
+    fn drop(&mut self) {
+        self.release_mouse_event();
+        // The render thread stops drawing into the layer before it's released.
+        self.render_thread = None;
+    }
+}
+
+#[cfg(target_os = "macos")]
 struct NSEventT<T: NSEvent + Copy> {
     raw_event: T,
     view: *mut Object,
+    geometry: Geometry,
 }
 
+#[cfg(target_os = "macos")]
 impl<T: NSEvent + Copy> From<NSEventT<T>> for Vec<Event> {
     fn from(event: NSEventT<T>) -> Self {
         unsafe {
             let mouse_location: NSPoint = NSEvent::locationInWindow(event.raw_event);
             let converted_location =
                 NSView::convertPoint_fromView_(event.view, mouse_location, nil);
+            let position = event.geometry.to_logical(Point::new(
+                converted_location.x as f32,
+                converted_location.y as f32,
+            ));
             let moved = Event::Mouse(mouse::Event::CursorMoved {
-                x: converted_location.x as f32,
-                y: converted_location.y as f32,
+                x: position.x,
+                y: position.y,
             });
             let button_num = NSEvent::buttonNumber(event.raw_event);
 
@@ -641,12 +2517,18 @@ impl<T: NSEvent + Copy> From<NSEventT<T>> for Vec<Event> {
                 NSEventType::NSMouseExited => vec![Event::Mouse(mouse::Event::CursorLeft)],
                 NSEventType::NSKeyDown => event.as_key_down(),
                 NSEventType::NSKeyUp => event.as_key_up(),
-                NSEventType::NSScrollWheel => vec![Event::Mouse(mouse::Event::WheelScrolled {
-                    delta: mouse::ScrollDelta::Pixels {
-                        x: NSEvent::scrollingDeltaX(event.raw_event) as f32,
-                        y: NSEvent::scrollingDeltaY(event.raw_event) as f32,
-                    },
-                })],
+                NSEventType::NSScrollWheel => {
+                    let delta = event.geometry.to_logical(Point::new(
+                        NSEvent::scrollingDeltaX(event.raw_event) as f32,
+                        NSEvent::scrollingDeltaY(event.raw_event) as f32,
+                    ));
+                    vec![Event::Mouse(mouse::Event::WheelScrolled {
+                        delta: mouse::ScrollDelta::Pixels {
+                            x: delta.x,
+                            y: delta.y,
+                        },
+                    })]
+                }
                 NSEventType::NSOtherMouseDown => vec![Event::Mouse(mouse::Event::ButtonPressed(
                     ButtonNumber(button_num).into(),
                 ))],
@@ -659,6 +2541,7 @@ impl<T: NSEvent + Copy> From<NSEventT<T>> for Vec<Event> {
     }
 }
 
+#[cfg(target_os = "macos")]
 impl<T: NSEvent + Copy> NSEventT<T> {
     unsafe fn as_key_down(self) -> Vec<Event> {
         let event = self.raw_event;
@@ -704,8 +2587,10 @@ impl<T: NSEvent + Copy> NSEventT<T> {
     }
 }
 
+#[cfg(target_os = "macos")]
 struct NSKeyCode(u16);
 
+#[cfg(target_os = "macos")]
 impl From<NSKeyCode> for Option<keyboard::KeyCode> {
     fn from(key_code: NSKeyCode) -> Self {
         match key_code.0 {
@@ -828,8 +2713,10 @@ impl From<NSKeyCode> for Option<keyboard::KeyCode> {
     }
 }
 
+#[cfg(target_os = "macos")]
 struct ModifierFlags(NSEventModifierFlags);
 
+#[cfg(target_os = "macos")]
 impl From<ModifierFlags> for keyboard::ModifiersState {
     fn from(flags: ModifierFlags) -> Self {
         Self {
@@ -841,8 +2728,10 @@ impl From<ModifierFlags> for keyboard::ModifiersState {
     }
 }
 
+#[cfg(target_os = "macos")]
 struct ButtonNumber(i64);
 
+#[cfg(target_os = "macos")]
 impl From<ButtonNumber> for mouse::Button {
     fn from(number: ButtonNumber) -> Self {
         match number.0 {
@@ -852,47 +2741,92 @@ impl From<ButtonNumber> for mouse::Button {
     }
 }
 
-struct Pasteboard {
-    object: id,
-}
-
-impl Pasteboard {
-    fn new() -> Self {
-        let object = unsafe { NSPasteboard::generalPasteboard(nil) };
-
-        Self { object }
+/// Returns the display showing the window of the view, if any.
+#[cfg(target_os = "macos")]
+unsafe fn get_nsview_display(view: *mut Object) -> Option<clock::CGDirectDisplayID> {
+    let window: id = msg_send![view, window];
+    if window.is_null() {
+        return None;
     }
+    let screen: id = msg_send![window, screen];
+    if screen.is_null() {
+        return None;
+    }
+    let description: id = msg_send![screen, deviceDescription];
+    let number: id = msg_send![description, objectForKey: nsstring("NSScreenNumber")];
+    if number.is_null() {
+        return None;
+    }
+
+    let display: u32 = msg_send![number, unsignedIntValue];
+    Some(display)
 }
 
-impl Clipboard for Pasteboard {
-    fn content(&self) -> Option<String> {
-        let ptr = unsafe {
-            let class = class!(NSString);
-            let class_ref: *mut Object = msg_send![class, self];
-            let classes = NSArray::arrayWithObject(nil, class_ref);
-            let objects = self.object.readObjectsForClasses_options(classes, nil);
-            if objects.is_null() || objects.count() == 0 {
-                return None;
-            }
-            NSString::UTF8String(objects.objectAtIndex(0))
-        };
+#[cfg(target_os = "macos")]
+unsafe fn nsstring(string: &str) -> id {
+    NSString::alloc(nil).init_str(string).autorelease()
+}
 
-        if ptr.is_null() {
-            None
-        } else {
-            unsafe { Some(CStr::from_ptr(ptr).to_string_lossy().to_string()) }
-        }
-    }
+#[cfg(target_os = "macos")]
+unsafe fn nsdata(data: &[u8]) -> id {
+    msg_send![class!(NSData), dataWithBytes: data.as_ptr() length: data.len() as NSUInteger]
 }
 
+#[cfg(target_os = "macos")]
 unsafe fn pathbuf_from_nsurl(url: *mut Object) -> PathBuf {
     let ptr: *const c_char = msg_send![url, fileSystemRepresentation];
     CStr::from_ptr(ptr).to_string_lossy().to_string().into()
 }
 
+/// Returns the size in points closest to `proposed` which the size hints allow.
+#[cfg(target_os = "macos")]
+fn constrain_size(geometry: Geometry, hints: &resize::SizeHints, proposed: Size) -> Size {
+    let geometry = geometry.with_size(proposed);
+    let size = hints.constrain(geometry.logical_size());
+    let size = geometry.to_points(Point::new(size.width, size.height));
+
+    Size::new(size.x, size.y)
+}
+
+/// Resizes the view, along with its window if it's the content view.
+///
+/// The top left corner stays put, so a resize grip keeps following the cursor.
+#[cfg(target_os = "macos")]
+unsafe fn resize_view(view: *mut Object, size: Size) {
+    let size = NSSize::new(size.width.into(), size.height.into());
+    let window: id = msg_send![view, window];
+    let content_view: id = if window.is_null() {
+        nil
+    } else {
+        msg_send![window, contentView]
+    };
+
+    if !window.is_null() && content_view == view {
+        let frame: NSRect = msg_send![window, frame];
+        let content: NSRect = msg_send![window, contentRectForFrameRect: frame];
+        let top = content.origin.y + content.size.height;
+        let content = NSRect::new(NSPoint::new(content.origin.x, top - size.height), size);
+        let frame: NSRect = msg_send![window, frameRectForContentRect: content];
+        let () = msg_send![window, setFrame: frame display: YES];
+    } else {
+        let () = msg_send![view, setFrameSize: size];
+    }
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn main_screen_scale_factor() -> f64 {
+    let screen: id = msg_send![class!(NSScreen), mainScreen];
+    if screen.is_null() {
+        1.0
+    } else {
+        msg_send![screen, backingScaleFactor]
+    }
+}
+
 /// This function returns scale factor of the passed view.
 ///
 /// It returns `None` if the view has no window.
+#[cfg(target_os = "macos")]
 pub unsafe fn get_nsview_scale_factor(view: *mut c_void) -> Option<f64> {
     let window: id = msg_send![view as *mut Object, window];
     if window.is_null() {
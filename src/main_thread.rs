@@ -0,0 +1,116 @@
+//! Make sure AppKit is only used from the main thread.
+//!
+//! Creating a view requires a [`MainThread`] marker, which can only be obtained on the main
+//! thread. `IcedView` can't be sent to other threads, so its methods are only called there too.
+//! Other threads can run work on the main thread with [`run`].
+//!
+//! [`MainThread`]: struct.MainThread.html
+//! [`run`]: fn.run.html
+
+#[cfg(target_os = "macos")]
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+#[cfg(target_os = "macos")]
+use cocoa::base::BOOL;
+#[cfg(target_os = "macos")]
+use objc::{class, msg_send, sel, sel_impl};
+
+/// A proof that the code runs on the main thread.
+///
+/// It has no size and can't leave the thread it was created on:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+///
+/// assert_send::<iced_nsview::MainThread>();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MainThread {
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl MainThread {
+    /// Returns the marker, or `None` if the current thread isn't the main thread.
+    #[cfg(target_os = "macos")]
+    pub fn new() -> Option<Self> {
+        if is_main_thread() {
+            Some(Self {
+                _not_send: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the marker without checking the current thread.
+    ///
+    /// # Safety
+    ///
+    /// The current thread must be the main thread, e.g. in a callback of AppKit.
+    pub unsafe fn new_unchecked() -> Self {
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// Returns `true` if the current thread is the main thread.
+#[cfg(target_os = "macos")]
+pub fn is_main_thread() -> bool {
+    let is_main_thread: BOOL = unsafe { msg_send![class!(NSThread), isMainThread] };
+
+    is_main_thread != 0
+}
+
+/// Runs `f` on the main thread, asynchronously.
+///
+/// `f` runs on a later iteration of the main run loop, even if `run` is called from the main
+/// thread.
+#[cfg(target_os = "macos")]
+pub fn run(f: impl FnOnce(MainThread) + Send + 'static) {
+    let work: Box<Box<dyn FnOnce(MainThread) + Send>> = Box::new(Box::new(f));
+
+    unsafe {
+        dispatch_async_f(
+            &_dispatch_main_q,
+            Box::into_raw(work) as *mut c_void,
+            run_work,
+        );
+    }
+}
+
+#[cfg(target_os = "macos")]
+extern "C" fn run_work(context: *mut c_void) {
+    let work = unsafe { Box::from_raw(context as *mut Box<dyn FnOnce(MainThread) + Send>) };
+
+    // The main queue only runs on the main thread. A panic mustn't unwind into libdispatch.
+    let _ = crate::poison::catch(|| work(unsafe { MainThread::new_unchecked() }));
+}
+
+/// Panics in debug builds if the current thread isn't the main thread.
+#[cfg(target_os = "macos")]
+pub(crate) fn assert_main_thread() {
+    debug_assert!(
+        is_main_thread(),
+        "AppKit was called from a thread which isn't the main thread"
+    );
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+struct DispatchQueue {
+    _private: [u8; 0],
+}
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    // What `dispatch_get_main_queue()` returns.
+    static _dispatch_main_q: DispatchQueue;
+
+    fn dispatch_async_f(
+        queue: *const DispatchQueue,
+        context: *mut c_void,
+        work: extern "C" fn(*mut c_void),
+    );
+}
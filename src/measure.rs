@@ -0,0 +1,116 @@
+//! Measure the size the content of the view wants to take.
+//!
+//! The measurement only lays the element tree out, so it doesn't need a window or a GPU. It
+//! works with any renderer, including `iced_native::renderer::Null`, e.g. in headless tests.
+//!
+//! The view reports the intrinsic size of the application as its `intrinsicContentSize`, so
+//! hosts using Auto Layout can size it, and can resize itself to it with [`Sizing::FitContent`].
+//!
+//! [`Sizing::FitContent`]: enum.Sizing.html#variant.FitContent
+
+use iced_native::{layout, Element};
+
+use crate::Size;
+
+/// How the size of the view is decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sizing {
+    /// The host decides the size of the view.
+    Fixed,
+    /// The view requests a resize whenever the intrinsic size of its content changes.
+    ///
+    /// The dimensions along which the content fills the available space keep their size.
+    FitContent,
+}
+
+impl Default for Sizing {
+    fn default() -> Self {
+        Sizing::Fixed
+    }
+}
+
+/// Returns the size the element takes within the given limits.
+pub fn measure<Message, Renderer>(
+    element: &Element<'_, Message, Renderer>,
+    renderer: &Renderer,
+    limits: &layout::Limits,
+) -> Size
+where
+    Renderer: iced_native::Renderer,
+{
+    element.layout(renderer, limits).size()
+}
+
+/// Returns the size the element takes without any constraint.
+///
+/// A dimension is infinite if the element fills the available space along it, as it has no
+/// intrinsic size then.
+pub fn intrinsic_size<Message, Renderer>(
+    element: &Element<'_, Message, Renderer>,
+    renderer: &Renderer,
+) -> Size
+where
+    Renderer: iced_native::Renderer,
+{
+    measure(
+        element,
+        renderer,
+        &layout::Limits::new(Size::ZERO, Size::INFINITY),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use iced_native::{renderer::Null, Column, Length, Row, Text};
+
+    // The null renderer measures every text as 0 wide and 20 high.
+    fn label(width: Length) -> Text {
+        Text::new("Label").width(width)
+    }
+
+    #[test]
+    fn measures_column() {
+        let element: Element<'_, (), Null> = Column::new()
+            .padding(5)
+            .spacing(10)
+            .push(label(Length::Units(100)))
+            .push(label(Length::Units(60)))
+            .into();
+
+        assert_eq!(intrinsic_size(&element, &Null), Size::new(110.0, 60.0));
+    }
+
+    #[test]
+    fn measures_row() {
+        let element: Element<'_, (), Null> = Row::new()
+            .spacing(10)
+            .push(label(Length::Units(100)))
+            .push(label(Length::Units(60)))
+            .into();
+
+        assert_eq!(intrinsic_size(&element, &Null), Size::new(170.0, 20.0));
+    }
+
+    #[test]
+    fn filling_has_no_intrinsic_size() {
+        let element: Element<'_, (), Null> = label(Length::Fill).into();
+
+        let size = intrinsic_size(&element, &Null);
+
+        assert!(size.width.is_infinite());
+        assert_eq!(size.height, 20.0);
+    }
+
+    #[test]
+    fn measures_within_limits() {
+        let element: Element<'_, (), Null> = Column::new()
+            .push(label(Length::Fill))
+            .push(label(Length::Units(500)))
+            .into();
+        let limits = layout::Limits::new(Size::ZERO, Size::new(300.0, 200.0));
+
+        assert_eq!(measure(&element, &Null, &limits), Size::new(300.0, 40.0));
+    }
+}
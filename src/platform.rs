@@ -0,0 +1,434 @@
+//! Create the Cocoa objects of a view, one step after another.
+//!
+//! A view is an instance of an `NSView` subclass, with a `CAMetalLayer` it renders to. The
+//! steps talking to AppKit are behind the [`Platform`] trait. [`validate`] checks the arguments
+//! before anything is created, and [`create`] releases the view if its layer can't be created,
+//! so `IcedView::try_new` leaves nothing behind on error.
+//!
+//! The instance variables of the view own what it holds on the Rust side, from [`into_ivar`]
+//! until `dealloc` frees it with [`free_ivars`].
+//!
+//! [`Platform`]: trait.Platform.html
+//! [`validate`]: fn.validate.html
+//! [`create`]: fn.create.html
+//! [`into_ivar`]: fn.into_ivar.html
+//! [`free_ivars`]: fn.free_ivars.html
+
+use std::any::TypeId;
+#[cfg(target_os = "macos")]
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::c_void;
+use std::hash::{Hash, Hasher};
+use std::ptr;
+
+#[cfg(target_os = "macos")]
+use objc::runtime::Class;
+
+use crate::{poison, Error, Geometry, Settings, Size};
+
+#[cfg(target_os = "macos")]
+thread_local! {
+    // Views are only created on the main thread, so their classes are registered there.
+    pub(crate) static CLASSES: RefCell<Classes<&'static Class>> = RefCell::new(Classes::new());
+}
+
+/// The objects a view is made of.
+pub(crate) trait Platform {
+    type View;
+    type Layer;
+
+    /// Creates a view of the given size, in points.
+    fn create_view(&mut self, size: Size) -> Result<Self::View, Error>;
+
+    /// Creates the layer the view renders to.
+    fn create_layer(&mut self, view: &Self::View, scale_factor: f64) -> Result<Self::Layer, Error>;
+
+    /// Releases a view which won't be returned to the host.
+    fn release_view(&mut self, view: Self::View);
+}
+
+/// Checks the arguments of a new view.
+pub(crate) fn validate(size: Size, settings: &Settings) -> Result<(), Error> {
+    let is_valid = |value: f32| value.is_finite() && value >= 0.0;
+    if !is_valid(size.width) || !is_valid(size.height) {
+        return Err(Error::InvalidSize(size));
+    }
+
+    settings.validate()
+}
+
+/// Creates a view and its layer.
+///
+/// If the layer can't be created, the view is released before the error is returned.
+pub(crate) fn create<P: Platform>(
+    platform: &mut P,
+    geometry: &Geometry,
+) -> Result<(P::View, P::Layer), Error> {
+    let view = platform.create_view(geometry.size())?;
+
+    match platform.create_layer(&view, geometry.scale_factor()) {
+        Ok(layer) => Ok((view, layer)),
+        Err(error) => {
+            platform.release_view(view);
+            Err(error)
+        }
+    }
+}
+
+/// The view classes registered by this copy of the crate, by application type.
+///
+/// The methods of a class are those of one application type, so a class is never shared with
+/// another type, nor with another copy of the crate loaded in the process.
+#[derive(Debug)]
+pub(crate) struct Classes<C> {
+    entries: Vec<(TypeId, C)>,
+}
+
+impl<C: Copy> Classes<C> {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the class of the application type, registered with `register` the first time.
+    ///
+    /// `register` returns `None` if the runtime refuses the class, e.g. because its name is
+    /// taken. Registering is tried again for the next view then.
+    pub(crate) fn get_or_register(
+        &mut self,
+        application: TypeId,
+        register: impl FnOnce() -> Option<C>,
+    ) -> Result<C, Error> {
+        if let Some((_, class)) = self.entries.iter().find(|(id, _)| *id == application) {
+            return Ok(*class);
+        }
+
+        let class = register().ok_or(Error::ClassRegistrationFailed)?;
+        self.entries.push((application, class));
+
+        Ok(class)
+    }
+}
+
+/// Returns the name of the class of an application type.
+///
+/// The runtime has one namespace for the classes of every binary loaded in the process, so the
+/// name is salted with something unique to the binary, e.g. the address of a function.
+pub(crate) fn class_name(type_name: &str, salt: usize) -> String {
+    let mut hasher = DefaultHasher::new();
+    type_name.hash(&mut hasher);
+
+    format!("IcedView{:016x}_{:x}", hasher.finish(), salt)
+}
+
+/// Moves a value to the heap, for an instance variable of a view to own it.
+pub(crate) fn into_ivar<T>(value: T) -> *mut c_void {
+    Box::into_raw(Box::new(value)) as *mut c_void
+}
+
+/// Takes back the value an instance variable owns, leaving the variable empty.
+///
+/// # Safety
+///
+/// The variable must be empty, or hold a value of type `T` set with `into_ivar`.
+pub(crate) unsafe fn take_ivar<T>(ivar: &mut *mut c_void) -> Option<Box<T>> {
+    let value = std::mem::replace(ivar, ptr::null_mut()) as *mut T;
+
+    if value.is_null() {
+        None
+    } else {
+        Some(Box::from_raw(value))
+    }
+}
+
+/// Frees what the instance variables of a view own: its event handler `E`, its initialization
+/// `I`, then its panic hook guard `G`. Empty variables are skipped, so it's freed once.
+///
+/// Dropping the application mustn't unwind into AppKit, so the panics of the first two drops
+/// are caught, while the hook still captures them.
+///
+/// # Safety
+///
+/// Each variable must be empty, or hold a value of its type set with `into_ivar`.
+pub(crate) unsafe fn free_ivars<E, I, G>(
+    event_handler: &mut *mut c_void,
+    init: &mut *mut c_void,
+    panic_hook: &mut *mut c_void,
+) {
+    let event_handler = take_ivar::<E>(event_handler);
+    let init = take_ivar::<I>(init);
+    let panic_hook = take_ivar::<G>(panic_hook);
+
+    let _ = poison::catch(|| drop(event_handler));
+    let _ = poison::catch(|| drop(init));
+    // The last view restores the panic hook the process had before.
+    drop(panic_hook);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Records the objects it creates and releases, and fails the steps it's told to.
+    #[derive(Debug, Default)]
+    struct Mock {
+        view_error: Option<Error>,
+        layer_error: Option<Error>,
+        views: Vec<Size>,
+        layers: Vec<(u32, f64)>,
+        released: Vec<u32>,
+    }
+
+    impl Platform for Mock {
+        type View = u32;
+        type Layer = u32;
+
+        fn create_view(&mut self, size: Size) -> Result<u32, Error> {
+            if let Some(error) = self.view_error.clone() {
+                return Err(error);
+            }
+            self.views.push(size);
+
+            Ok(self.views.len() as u32)
+        }
+
+        fn create_layer(&mut self, view: &u32, scale_factor: f64) -> Result<u32, Error> {
+            if let Some(error) = self.layer_error.clone() {
+                return Err(error);
+            }
+            self.layers.push((*view, scale_factor));
+
+            Ok(self.layers.len() as u32)
+        }
+
+        fn release_view(&mut self, view: u32) {
+            self.released.push(view);
+        }
+    }
+
+    fn geometry() -> Geometry {
+        Geometry::new(Size::new(400.0, 300.0), 2.0)
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        let settings = Settings::default();
+
+        for size in &[
+            Size::new(-1.0, 300.0),
+            Size::new(400.0, f32::NAN),
+            Size::new(f32::INFINITY, 300.0),
+        ] {
+            assert_eq!(validate(*size, &settings), Err(Error::InvalidSize(*size)));
+        }
+        assert_eq!(validate(Size::ZERO, &settings), Ok(()));
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let settings = Settings {
+            sample_count: 3,
+            ..Settings::default()
+        };
+
+        assert_eq!(
+            validate(Size::new(400.0, 300.0), &settings),
+            Err(Error::InvalidSampleCount(3))
+        );
+    }
+
+    #[test]
+    fn creates_view_and_layer() {
+        let mut mock = Mock::default();
+
+        assert_eq!(create(&mut mock, &geometry()), Ok((1, 1)));
+        assert_eq!(mock.views, vec![Size::new(400.0, 300.0)]);
+        assert_eq!(mock.layers, vec![(1, 2.0)]);
+        assert!(mock.released.is_empty());
+    }
+
+    #[test]
+    fn fails_without_view() {
+        let mut mock = Mock {
+            view_error: Some(Error::ClassRegistrationFailed),
+            ..Mock::default()
+        };
+
+        assert_eq!(
+            create(&mut mock, &geometry()),
+            Err(Error::ClassRegistrationFailed)
+        );
+        assert!(mock.layers.is_empty());
+        assert!(mock.released.is_empty());
+    }
+
+    #[test]
+    fn releases_view_without_layer() {
+        let mut mock = Mock {
+            layer_error: Some(Error::SurfaceCreationFailed),
+            ..Mock::default()
+        };
+
+        assert_eq!(
+            create(&mut mock, &geometry()),
+            Err(Error::SurfaceCreationFailed)
+        );
+        assert_eq!(mock.released, vec![1]);
+    }
+
+    struct First;
+    struct Second;
+
+    #[test]
+    fn registers_class_once_per_application() {
+        let mut classes = Classes::new();
+
+        assert_eq!(
+            classes.get_or_register(TypeId::of::<First>(), || Some(1)),
+            Ok(1)
+        );
+        assert_eq!(
+            classes.get_or_register(TypeId::of::<First>(), || panic!("registered twice")),
+            Ok(1)
+        );
+        assert_eq!(
+            classes.get_or_register(TypeId::of::<Second>(), || Some(2)),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn never_adopts_refused_class() {
+        let mut classes = Classes::new();
+
+        // Another copy of the crate took the name.
+        assert_eq!(
+            classes.get_or_register(TypeId::of::<First>(), || None),
+            Err(Error::ClassRegistrationFailed)
+        );
+        assert_eq!(
+            classes.get_or_register(TypeId::of::<First>(), || Some(1)),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn salts_class_names() {
+        let name = class_name("app::Editor", 0x1000);
+
+        assert!(name.starts_with("IcedView"));
+        assert!(name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_'));
+        assert_eq!(name, class_name("app::Editor", 0x1000));
+        assert_ne!(name, class_name("app::Editor", 0x2000));
+        assert_ne!(name, class_name("app::Other", 0x1000));
+    }
+
+    /// Records its name when it's dropped, and panics if told to.
+    struct Owned {
+        name: &'static str,
+        drops: Rc<RefCell<Vec<&'static str>>>,
+        panics: bool,
+    }
+
+    impl Drop for Owned {
+        fn drop(&mut self) {
+            self.drops.borrow_mut().push(self.name);
+            if self.panics {
+                panic!("dropping {}", self.name);
+            }
+        }
+    }
+
+    /// The instance variables of a view.
+    struct View {
+        event_handler: *mut c_void,
+        init: *mut c_void,
+        panic_hook: *mut c_void,
+        drops: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl View {
+        fn new() -> Self {
+            let drops = Rc::new(RefCell::new(Vec::new()));
+            let owned = |name| {
+                into_ivar(Owned {
+                    name,
+                    drops: Rc::clone(&drops),
+                    panics: false,
+                })
+            };
+
+            Self {
+                event_handler: ptr::null_mut(),
+                init: owned("init"),
+                panic_hook: owned("panic hook"),
+                drops,
+            }
+        }
+
+        /// Sets the event handler, like `IcedView::start`. The initialization, completed, is
+        /// kept until `dealloc`.
+        fn start(&mut self, panics: bool) {
+            self.event_handler = into_ivar(Owned {
+                name: "event handler",
+                drops: Rc::clone(&self.drops),
+                panics,
+            });
+        }
+
+        fn dealloc(&mut self) {
+            unsafe {
+                free_ivars::<Owned, Owned, Owned>(
+                    &mut self.event_handler,
+                    &mut self.init,
+                    &mut self.panic_hook,
+                );
+            }
+        }
+
+        fn drops(&self) -> Vec<&'static str> {
+            self.drops.borrow().clone()
+        }
+    }
+
+    #[test]
+    fn frees_ivars_once() {
+        let mut view = View::new();
+        view.start(false);
+
+        view.dealloc();
+        assert_eq!(view.drops(), vec!["event handler", "init", "panic hook"]);
+        assert!(view.event_handler.is_null());
+        assert!(view.init.is_null());
+        assert!(view.panic_hook.is_null());
+
+        view.dealloc();
+        assert_eq!(view.drops().len(), 3);
+    }
+
+    #[test]
+    fn frees_ivars_of_view_deallocated_while_loading() {
+        let mut view = View::new();
+
+        view.dealloc();
+
+        assert_eq!(view.drops(), vec!["init", "panic hook"]);
+    }
+
+    #[test]
+    fn frees_ivars_after_panicking_drop() {
+        let mut view = View::new();
+        view.start(true);
+
+        view.dealloc();
+
+        assert_eq!(view.drops(), vec!["event handler", "init", "panic hook"]);
+    }
+}
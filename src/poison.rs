@@ -0,0 +1,380 @@
+//! Keep panics of the application from unwinding into AppKit.
+//!
+//! Every method AppKit calls on the view catches the panics of the application. The first one
+//! poisons the view: it stops handling events and shows an error screen instead of the user
+//! interface, until the host recovers it with a new application. A hook set with [`set_hook`]
+//! receives every caught [`Panic`], e.g. to send it to a crash reporter.
+//!
+//! While a view is alive, a panic hook of this crate captures the location and the backtrace of
+//! the panics it catches. The hook lives in the binary of this crate, which the host may unload
+//! once the views are gone, e.g. along with a plugin, so the previous hook is restored when the
+//! last view is deallocated.
+//!
+//! [`set_hook`]: fn.set_hook.html
+//! [`Panic`]: struct.Panic.html
+
+#[cfg(any(target_os = "macos", test))]
+use std::any::Any;
+#[cfg(any(target_os = "macos", test))]
+use std::backtrace::Backtrace;
+#[cfg(any(target_os = "macos", test))]
+use std::cell::{Cell, RefCell};
+use std::fmt;
+#[cfg(any(target_os = "macos", test))]
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+type Hook = Arc<dyn Fn(&Panic) + Send + Sync>;
+#[cfg(any(target_os = "macos", test))]
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Send + Sync + 'static>;
+
+static HOOK: Mutex<Option<Hook>> = Mutex::new(None);
+#[cfg(any(target_os = "macos", test))]
+static PANIC_HOOK: Mutex<Installation> = Mutex::new(Installation::new());
+
+#[cfg(any(target_os = "macos", test))]
+thread_local! {
+    // The number of nested calls catching panics on this thread.
+    static CATCHING: Cell<usize> = Cell::new(0);
+    // The panic captured by the panic hook, until it's caught.
+    static CAUGHT: RefCell<Option<Panic>> = RefCell::new(None);
+}
+
+/// A panic caught at the boundary with AppKit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Panic {
+    message: String,
+    location: Option<String>,
+    backtrace: String,
+}
+
+impl Panic {
+    /// Returns the message of the panic.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the file, line and column where the panic happened, if known.
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// Returns the backtrace captured when the panic happened.
+    ///
+    /// It's empty if the panic wasn't captured by the panic hook of this crate, e.g. because
+    /// another hook replaced it.
+    pub fn backtrace(&self) -> &str {
+        &self.backtrace
+    }
+
+    #[cfg(any(target_os = "macos", test))]
+    fn from_info(info: &PanicHookInfo<'_>) -> Self {
+        Self {
+            message: message(info.payload()),
+            location: info.location().map(|location| location.to_string()),
+            backtrace: Backtrace::force_capture().to_string(),
+        }
+    }
+
+    #[cfg(any(target_os = "macos", test))]
+    fn from_payload(payload: &(dyn Any + Send)) -> Self {
+        Self {
+            message: message(payload),
+            location: None,
+            backtrace: String::new(),
+        }
+    }
+}
+
+impl fmt::Display for Panic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{} at {}", self.message, location),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Sets the function receiving the panics caught by every view of the process.
+///
+/// It's called right after the panic poisons the view, on the main thread. Panics of wgpu
+/// caught while creating a GPU device or rendering a frame are reported too, on the thread
+/// which caught them.
+pub fn set_hook(f: impl Fn(&Panic) + Send + Sync + 'static) {
+    *lock(&HOOK) = Some(Arc::new(f));
+}
+
+/// Removes the function set with `set_hook`.
+pub fn take_hook() {
+    lock(&HOOK).take();
+}
+
+/// Whether a view handles events or shows an error screen because of a panic.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug, Default)]
+pub(crate) struct Poison {
+    panic: Option<Panic>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl Poison {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the panic which poisoned the view, if any.
+    pub(crate) fn panic(&self) -> Option<&Panic> {
+        self.panic.as_ref()
+    }
+
+    /// Returns `true` if a panic poisoned the view.
+    pub(crate) fn is_poisoned(&self) -> bool {
+        self.panic.is_some()
+    }
+
+    /// Poisons the view, unless an earlier panic already did.
+    pub(crate) fn poison(&mut self, panic: Panic) {
+        if self.panic.is_none() {
+            self.panic = Some(panic);
+        }
+    }
+
+    /// Returns the view to a healthy state, and the panic which poisoned it, if any.
+    pub(crate) fn recover(&mut self) -> Option<Panic> {
+        self.panic.take()
+    }
+}
+
+/// Runs `f`, catching its panic and reporting it to the hook.
+///
+/// The location and the backtrace of the panic are only captured while a `HookGuard` is alive.
+#[cfg(any(target_os = "macos", test))]
+pub(crate) fn catch<R>(f: impl FnOnce() -> R) -> Result<R, Panic> {
+    CATCHING.with(|catching| catching.set(catching.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(catching.get() - 1));
+
+    result.map_err(|payload| {
+        let panic = CAUGHT
+            .with(|caught| caught.borrow_mut().take())
+            .unwrap_or_else(|| Panic::from_payload(payload.as_ref()));
+
+        let hook = lock(&HOOK).clone();
+        if let Some(hook) = hook {
+            // A panicking hook mustn't unwind into AppKit either.
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(&panic)));
+        }
+
+        panic
+    })
+}
+
+/// Keeps the panic hook of this crate installed while it's alive. Every view holds one.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug)]
+pub(crate) struct HookGuard {
+    _private: (),
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl HookGuard {
+    pub(crate) fn new() -> Self {
+        let mut installation = lock(&PANIC_HOOK);
+        if installation.guards == 0 {
+            installation.install();
+        }
+        installation.guards += 1;
+
+        Self { _private: () }
+    }
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl Drop for HookGuard {
+    fn drop(&mut self) {
+        let mut installation = lock(&PANIC_HOOK);
+        installation.guards -= 1;
+        if installation.guards == 0 {
+            installation.uninstall();
+        }
+    }
+}
+
+/// The panic hook of this crate, and the one it replaced.
+#[cfg(any(target_os = "macos", test))]
+struct Installation {
+    guards: usize,
+    // The previous hook, and the address of the hook of this crate, while it's installed.
+    previous: Option<(Arc<PanicHook>, usize)>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl Installation {
+    const fn new() -> Self {
+        Self {
+            guards: 0,
+            previous: None,
+        }
+    }
+
+    /// Captures the panics happening within `catch`, and leaves the others to the previous hook.
+    fn install(&mut self) {
+        let previous = Arc::new(panic::take_hook());
+        let hook: PanicHook = {
+            let previous = Arc::clone(&previous);
+
+            Box::new(move |info: &PanicHookInfo<'_>| {
+                if CATCHING.with(Cell::get) > 0 {
+                    let panic = Panic::from_info(info);
+                    CAUGHT.with(|caught| *caught.borrow_mut() = Some(panic));
+                } else {
+                    previous(info);
+                }
+            })
+        };
+        let address = address(&hook);
+
+        panic::set_hook(hook);
+        self.previous = Some((previous, address));
+    }
+
+    /// Restores the previous hook, unless the hook of this crate was replaced in the meantime.
+    ///
+    /// Whoever replaced it is then responsible for the hook they replaced.
+    fn uninstall(&mut self) {
+        let (previous, installed) = match self.previous.take() {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let current = panic::take_hook();
+        if address(&current) != installed {
+            panic::set_hook(current);
+            return;
+        }
+
+        // Dropping the hook of this crate leaves the previous one with a single owner.
+        drop(current);
+        if let Ok(previous) = Arc::try_unwrap(previous) {
+            panic::set_hook(previous);
+        }
+    }
+}
+
+/// Identifies a panic hook by the address of its closure. The closure of this crate captures
+/// the previous hook, so its address is unique.
+#[cfg(any(target_os = "macos", test))]
+fn address(hook: &PanicHook) -> usize {
+    let hook: *const (dyn Fn(&PanicHookInfo<'_>) + Send + Sync) = &**hook;
+
+    hook as *const () as usize
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("Box<dyn Any>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    // The panic hook and the hook of this crate are global.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn poisons_once() {
+        let mut poison = Poison::new();
+        assert!(!poison.is_poisoned());
+
+        poison.poison(Panic::from_payload(&"first"));
+        poison.poison(Panic::from_payload(&"second"));
+
+        assert!(poison.is_poisoned());
+        assert_eq!(poison.panic().map(Panic::message), Some("first"));
+    }
+
+    #[test]
+    fn recovers() {
+        let mut poison = Poison::new();
+        assert!(poison.recover().is_none());
+
+        poison.poison(Panic::from_payload(&"first"));
+
+        assert_eq!(poison.recover().as_ref().map(Panic::message), Some("first"));
+        assert!(!poison.is_poisoned());
+
+        poison.poison(Panic::from_payload(&"second"));
+
+        assert_eq!(poison.panic().map(Panic::message), Some("second"));
+    }
+
+    #[test]
+    fn catches_panics() {
+        let _serial = lock(&SERIAL);
+        let _guard = HookGuard::new();
+
+        assert_eq!(catch(|| 42).ok(), Some(42));
+
+        let panic = catch(|| panic!("caught {}", 42)).unwrap_err();
+
+        assert_eq!(panic.message(), "caught 42");
+        assert!(panic.location().unwrap().contains("poison.rs"));
+        assert!(!panic.backtrace().is_empty());
+    }
+
+    #[test]
+    fn reports_caught_panics() {
+        let _serial = lock(&SERIAL);
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        set_hook({
+            let reported = Arc::clone(&reported);
+            move |panic: &Panic| lock(&reported).push(panic.message().to_owned())
+        });
+
+        let _ = catch(|| panic!("reported"));
+        take_hook();
+        let _ = catch(|| panic!("unreported"));
+
+        assert_eq!(*lock(&reported), vec![String::from("reported")]);
+    }
+
+    #[test]
+    fn restores_previous_panic_hook() {
+        let _serial = lock(&SERIAL);
+        let previous = Arc::new(Mutex::new(Vec::new()));
+        panic::set_hook({
+            let previous = Arc::clone(&previous);
+            Box::new(move |info: &PanicHookInfo<'_>| {
+                lock(&previous).push(message(info.payload()));
+            })
+        });
+
+        let first = HookGuard::new();
+        let second = HookGuard::new();
+        let _ = catch(|| panic!("caught"));
+        let _ = thread::spawn(|| panic!("uncaught")).join();
+        drop(first);
+        let _ = catch(|| panic!("caught again"));
+        drop(second);
+        let _ = thread::spawn(|| panic!("restored")).join();
+
+        drop(panic::take_hook());
+        assert_eq!(
+            *lock(&previous),
+            vec![String::from("uncaught"), String::from("restored")]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Process the events and messages of a view as soon as they're queued.
+//!
+//! AppKit callbacks queue events, and commands queue messages. Both are processed before the
+//! callback returns, rather than when AppKit decides to redraw, so `Application::update` runs
+//! right away, e.g. for the automation of a host. Rendering is only scheduled.
+//!
+//! The order is kept:
+//!
+//! - events held by the coalescer are handed to the user interface before the ones which can't
+//!   wait, and before the messages queued after them,
+//! - the messages queued by the actions of an update are processed in the same pass, up to
+//!   [`MAX_PASSES`] updates, so messages which keep queuing more can't hang the callback. The
+//!   rest wait for the next frame.
+//!
+//! [`MAX_PASSES`]: constant.MAX_PASSES.html
+
+use iced_native::Event;
+
+use crate::coalesce::Coalescer;
+
+/// The most updates a callback runs before it returns to AppKit.
+pub(crate) const MAX_PASSES: usize = 16;
+
+/// The queue of the user interface of a view.
+pub(crate) trait Queue {
+    /// Returns the coalescer holding the events which can wait for the next frame.
+    fn coalescer(&mut self) -> &mut Coalescer;
+
+    /// Hands an event to the user interface.
+    fn deliver_event(&mut self, event: Event);
+
+    /// Returns `true` if no event or message waits for an update.
+    fn is_queue_empty(&self) -> bool;
+
+    /// Updates the user interface with the queued events and messages, and performs the
+    /// actions of their commands, which may queue more messages.
+    fn update(&mut self);
+}
+
+/// Adds events after the pending ones, and hands them all to the user interface if any of
+/// them can't wait for the next frame.
+pub(crate) fn push<Q: Queue>(queue: &mut Q, events: Vec<Event>) {
+    let mut is_urgent = false;
+    for event in events {
+        is_urgent |= queue.coalescer().push(event);
+    }
+
+    if is_urgent {
+        flush(queue);
+    }
+}
+
+/// Hands the events held by the coalescer to the user interface.
+pub(crate) fn flush<Q: Queue>(queue: &mut Q) {
+    for event in queue.coalescer().take() {
+        queue.deliver_event(event);
+    }
+}
+
+/// Updates the user interface until its queue is empty, or `MAX_PASSES` times, and returns
+/// `true` if it was updated.
+///
+/// Queued messages are handled after the events held before them. Otherwise, held events wait
+/// for the next frame, like the messages left in the queue.
+pub(crate) fn process<Q: Queue>(queue: &mut Q) -> bool {
+    if !queue.is_queue_empty() {
+        flush(queue);
+    }
+
+    let mut passes = 0;
+    while !queue.is_queue_empty() && passes < MAX_PASSES {
+        queue.update();
+        passes += 1;
+    }
+
+    passes > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::coalesce::Coalescing;
+    use crate::mouse;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Handled {
+        Event(Event),
+        Message(u32),
+    }
+
+    /// Handles events before messages, like `program::State`, and queues the next message
+    /// after the messages below 3, like a command would. Message 0 always queues itself again.
+    struct Mock {
+        coalescer: Coalescer,
+        events: Vec<Event>,
+        messages: Vec<u32>,
+        handled: Vec<Handled>,
+        updates: usize,
+    }
+
+    impl Mock {
+        fn new() -> Self {
+            Self {
+                coalescer: Coalescer::new(Coalescing::all()),
+                events: Vec::new(),
+                messages: Vec::new(),
+                handled: Vec::new(),
+                updates: 0,
+            }
+        }
+    }
+
+    impl Queue for Mock {
+        fn coalescer(&mut self) -> &mut Coalescer {
+            &mut self.coalescer
+        }
+
+        fn deliver_event(&mut self, event: Event) {
+            self.events.push(event);
+        }
+
+        fn is_queue_empty(&self) -> bool {
+            self.events.is_empty() && self.messages.is_empty()
+        }
+
+        fn update(&mut self) {
+            self.updates += 1;
+            self.handled
+                .extend(self.events.drain(..).map(Handled::Event));
+
+            for message in std::mem::take(&mut self.messages) {
+                self.handled.push(Handled::Message(message));
+                if message == 0 {
+                    self.messages.push(0);
+                } else if message < 3 {
+                    self.messages.push(message + 1);
+                }
+            }
+        }
+    }
+
+    fn moved(x: f32) -> Event {
+        Event::Mouse(mouse::Event::CursorMoved { x, y: 0.0 })
+    }
+
+    fn pressed() -> Event {
+        Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+    }
+
+    #[test]
+    fn holds_events_which_can_wait() {
+        let mut mock = Mock::new();
+
+        push(&mut mock, vec![moved(1.0), moved(2.0)]);
+        assert!(!process(&mut mock));
+
+        assert!(mock.handled.is_empty());
+        assert!(!mock.coalescer.is_empty());
+    }
+
+    #[test]
+    fn urgent_events_follow_held_ones() {
+        let mut mock = Mock::new();
+
+        push(&mut mock, vec![moved(1.0), moved(2.0)]);
+        push(&mut mock, vec![pressed()]);
+        assert!(process(&mut mock));
+
+        assert_eq!(
+            mock.handled,
+            vec![Handled::Event(moved(2.0)), Handled::Event(pressed())]
+        );
+        assert!(mock.coalescer.is_empty());
+    }
+
+    #[test]
+    fn messages_follow_held_events() {
+        let mut mock = Mock::new();
+
+        push(&mut mock, vec![moved(1.0)]);
+        mock.messages.push(10);
+        assert!(process(&mut mock));
+
+        assert_eq!(
+            mock.handled,
+            vec![Handled::Event(moved(1.0)), Handled::Message(10)]
+        );
+        assert_eq!(mock.updates, 1);
+    }
+
+    #[test]
+    fn processes_messages_of_actions_in_same_pass() {
+        let mut mock = Mock::new();
+
+        mock.messages.push(1);
+        assert!(process(&mut mock));
+
+        assert_eq!(
+            mock.handled,
+            vec![
+                Handled::Message(1),
+                Handled::Message(2),
+                Handled::Message(3)
+            ]
+        );
+        assert_eq!(mock.updates, 3);
+        assert!(mock.is_queue_empty());
+    }
+
+    #[test]
+    fn defers_messages_queuing_themselves_to_the_next_frame() {
+        let mut mock = Mock::new();
+
+        mock.messages.push(0);
+        assert!(process(&mut mock));
+        assert_eq!(mock.updates, MAX_PASSES);
+        assert!(!mock.is_queue_empty());
+
+        assert!(process(&mut mock));
+        assert_eq!(mock.updates, 2 * MAX_PASSES);
+    }
+
+    #[test]
+    fn frame_flushes_held_events() {
+        let mut mock = Mock::new();
+
+        push(&mut mock, vec![moved(1.0), moved(2.0)]);
+        flush(&mut mock);
+        assert!(process(&mut mock));
+
+        assert_eq!(mock.handled, vec![Handled::Event(moved(2.0))]);
+    }
+}
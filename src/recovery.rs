@@ -0,0 +1,358 @@
+//! Recover from failures to render a frame.
+//!
+//! A frame can fail to render because the swap chain timed out or doesn't match the surface
+//! anymore, or because the GPU device was lost, e.g. when an external GPU is unplugged. The view
+//! classifies the failure, then escalates its response as failures repeat: it retries, recreates
+//! the swap chain, then recreates the device along with the renderer, whose glyph and image
+//! caches fill up again as the next frames draw. Like the first one, the device is created on a
+//! background thread, and frames wait for it. Attempts are spaced out with an exponential
+//! backoff. Once a frame renders again, `Application::on_recovery` receives a [`Recovery`].
+//!
+//! If recreating the device a few times in a row doesn't help, the view stops trying on its
+//! own, and tries again from the start on the next change of the user interface.
+//!
+//! wgpu panics when the device fails while drawing, but so do bugs, e.g. in the primitives of
+//! the application. A panic while drawing only counts as a failure if [`diagnose`] blames the
+//! GPU. Otherwise, it poisons the view.
+//!
+//! [`Recovery`]: struct.Recovery.html
+//! [`diagnose`]: fn.diagnose.html
+
+#[cfg(any(target_os = "macos", test))]
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "macos")]
+use cocoa::base::{id, BOOL, NO};
+#[cfg(target_os = "macos")]
+use core_graphics::geometry::CGSize;
+#[cfg(target_os = "macos")]
+use objc::{msg_send, sel, sel_impl};
+
+#[cfg(target_os = "macos")]
+use crate::{Object, Size};
+
+/// Why a frame couldn't be rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The swap chain didn't provide a texture in time.
+    Timeout,
+    /// The swap chain doesn't match the size of the surface anymore.
+    Outdated,
+    /// The surface isn't attached to a device anymore.
+    Lost,
+    /// The GPU device was removed, or failed while rendering.
+    DeviceLost,
+}
+
+/// What the view does about a failure, from the lightest to the heaviest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    /// Tries again with the same swap chain.
+    Retry,
+    /// Recreates the swap chain.
+    RecreateSwapChain,
+    /// Recreates the device, the renderer and the swap chain.
+    RecreateDevice,
+}
+
+/// How the view got to render again after failing to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recovery {
+    failure: Failure,
+    action: Action,
+    failures: u32,
+}
+
+impl Recovery {
+    /// Returns the last failure.
+    pub fn failure(&self) -> Failure {
+        self.failure
+    }
+
+    /// Returns the heaviest action it took.
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    /// Returns the number of consecutive frames which failed.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+}
+
+/// Decides what to do about consecutive failures, and when to try again.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug, Default)]
+pub(crate) struct Policy {
+    failures: u32,
+    device_recreations: u32,
+    retry_at: Option<Instant>,
+    recovery: Option<Recovery>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl Policy {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a failure happening at `now`, and returns what to do before the next attempt.
+    ///
+    /// Returns `None` once the device was recreated too many times in a row: the policy starts
+    /// over, and nothing is attempted until the user interface changes.
+    pub(crate) fn on_failure(&mut self, failure: Failure, now: Instant) -> Option<Action> {
+        self.failures += 1;
+
+        let action = match failure {
+            Failure::Timeout if self.failures <= RETRIES => Action::Retry,
+            Failure::Timeout | Failure::Outdated | Failure::Lost
+                if self.failures <= RETRIES + SWAP_CHAIN_ATTEMPTS =>
+            {
+                Action::RecreateSwapChain
+            }
+            _ => Action::RecreateDevice,
+        };
+        if action == Action::RecreateDevice {
+            if self.device_recreations >= DEVICE_RECREATIONS {
+                *self = Self::new();
+                return None;
+            }
+            self.device_recreations += 1;
+        }
+        let heaviest = match self.recovery {
+            Some(recovery) => recovery.action.max(action),
+            None => action,
+        };
+
+        self.retry_at = Some(now + backoff(self.failures));
+        self.recovery = Some(Recovery {
+            failure,
+            action: heaviest,
+            failures: self.failures,
+        });
+
+        Some(action)
+    }
+
+    /// Records a rendered frame, and returns the recovery it completes, if frames failed
+    /// before.
+    pub(crate) fn on_success(&mut self) -> Option<Recovery> {
+        let recovery = self.recovery.take();
+        *self = Self::new();
+
+        recovery
+    }
+
+    /// Returns `true` while frames fail.
+    pub(crate) fn is_recovering(&self) -> bool {
+        self.failures > 0
+    }
+
+    /// Returns `true` if a frame may be attempted at `now`.
+    pub(crate) fn is_due(&self, now: Instant) -> bool {
+        self.retry_at.map_or(true, |retry_at| now >= retry_at)
+    }
+
+    /// Returns when the next frame may be attempted.
+    pub(crate) fn retry_at(&self) -> Option<Instant> {
+        self.retry_at
+    }
+}
+
+// The timeouts retried with the same swap chain, and the failures after them answered with a
+// new swap chain, before the device is recreated.
+#[cfg(any(target_os = "macos", test))]
+const RETRIES: u32 = 2;
+#[cfg(any(target_os = "macos", test))]
+const SWAP_CHAIN_ATTEMPTS: u32 = 2;
+// The devices recreated in a row before giving up.
+#[cfg(any(target_os = "macos", test))]
+const DEVICE_RECREATIONS: u32 = 3;
+
+#[cfg(any(target_os = "macos", test))]
+const INITIAL_BACKOFF: Duration = Duration::from_millis(16);
+#[cfg(any(target_os = "macos", test))]
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Returns the time to wait after the given number of consecutive failures.
+///
+/// The first failure is tried again right away.
+#[cfg(any(target_os = "macos", test))]
+fn backoff(failures: u32) -> Duration {
+    if failures <= 1 {
+        return Duration::from_secs(0);
+    }
+
+    let factor = 1u32 << (failures - 2).min(16);
+    (INITIAL_BACKOFF * factor).min(MAX_BACKOFF)
+}
+
+/// Returns the failure behind a panic while drawing, from what `diagnose` found, or `None` if
+/// the GPU isn't to blame.
+#[cfg(any(target_os = "macos", test))]
+pub(crate) fn panic_failure(diagnosis: Failure) -> Option<Failure> {
+    match diagnosis {
+        Failure::Lost | Failure::DeviceLost => Some(diagnosis),
+        // The layer and its device are fine.
+        Failure::Timeout | Failure::Outdated => None,
+    }
+}
+
+/// Tells why a `CAMetalLayer` didn't provide a texture for a swap chain of the given size.
+///
+/// The swap chain only reports a timeout, whatever happened, so the layer is inspected.
+#[cfg(target_os = "macos")]
+pub(crate) unsafe fn diagnose(layer: *mut Object, size: Size<u32>) -> Failure {
+    let device: id = msg_send![layer, device];
+    if device.is_null() {
+        return Failure::Lost;
+    }
+
+    // Available since macOS 10.13.
+    let can_be_removed: BOOL = msg_send![device, respondsToSelector: sel!(isRemoved)];
+    if can_be_removed != NO {
+        let is_removed: BOOL = msg_send![device, isRemoved];
+        if is_removed != NO {
+            return Failure::DeviceLost;
+        }
+    }
+
+    let drawable_size: CGSize = msg_send![layer, drawableSize];
+    if drawable_size.width as u32 != size.width || drawable_size.height as u32 != size.height {
+        Failure::Outdated
+    } else {
+        Failure::Timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn after(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn escalates_repeated_failures() {
+        let mut policy = Policy::new();
+        let now = Instant::now();
+        let actions: Vec<_> = (0..7)
+            .map(|_| policy.on_failure(Failure::Timeout, now))
+            .collect();
+
+        assert_eq!(
+            actions,
+            vec![
+                Some(Action::Retry),
+                Some(Action::Retry),
+                Some(Action::RecreateSwapChain),
+                Some(Action::RecreateSwapChain),
+                Some(Action::RecreateDevice),
+                Some(Action::RecreateDevice),
+                Some(Action::RecreateDevice),
+            ]
+        );
+        assert!(policy.is_recovering());
+    }
+
+    #[test]
+    fn recreates_device_when_it_is_lost() {
+        let mut policy = Policy::new();
+        let now = Instant::now();
+
+        assert_eq!(
+            policy.on_failure(Failure::Outdated, now),
+            Some(Action::RecreateSwapChain)
+        );
+        assert_eq!(
+            policy.on_failure(Failure::DeviceLost, now),
+            Some(Action::RecreateDevice)
+        );
+    }
+
+    #[test]
+    fn gives_up_after_device_recreations() {
+        let mut policy = Policy::new();
+        let now = Instant::now();
+
+        for _ in 0..DEVICE_RECREATIONS {
+            assert_eq!(
+                policy.on_failure(Failure::DeviceLost, now),
+                Some(Action::RecreateDevice)
+            );
+        }
+        assert_eq!(policy.on_failure(Failure::DeviceLost, now), None);
+
+        // The next frame starts over.
+        assert!(!policy.is_recovering());
+        assert!(policy.is_due(now));
+        assert_eq!(policy.on_success(), None);
+        assert_eq!(
+            policy.on_failure(Failure::Timeout, now),
+            Some(Action::Retry)
+        );
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_a_cap() {
+        assert_eq!(backoff(1), Duration::from_secs(0));
+        assert_eq!(backoff(2), Duration::from_millis(16));
+        assert_eq!(backoff(3), Duration::from_millis(32));
+        assert_eq!(backoff(8), Duration::from_millis(1024));
+        assert_eq!(backoff(9), Duration::from_secs(2));
+        assert_eq!(backoff(u32::MAX), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn waits_for_backoff() {
+        let mut policy = Policy::new();
+        let start = Instant::now();
+        assert!(policy.is_due(start));
+
+        // The first failure is tried again right away.
+        policy.on_failure(Failure::Timeout, start);
+        assert_eq!(policy.retry_at(), Some(start));
+        assert!(policy.is_due(start));
+
+        policy.on_failure(Failure::Timeout, after(start, 10));
+        assert_eq!(policy.retry_at(), Some(after(start, 26)));
+        assert!(!policy.is_due(after(start, 25)));
+        assert!(policy.is_due(after(start, 26)));
+    }
+
+    #[test]
+    fn success_reports_heaviest_recovery() {
+        let mut policy = Policy::new();
+        let now = Instant::now();
+        assert_eq!(policy.on_success(), None);
+
+        policy.on_failure(Failure::DeviceLost, now);
+        policy.on_failure(Failure::Timeout, now);
+        let recovery = policy.on_success().unwrap();
+
+        assert_eq!(recovery.failure(), Failure::Timeout);
+        assert_eq!(recovery.action(), Action::RecreateDevice);
+        assert_eq!(recovery.failures(), 2);
+
+        // The next failures start over.
+        assert!(!policy.is_recovering());
+        assert_eq!(policy.retry_at(), None);
+        assert_eq!(policy.on_success(), None);
+        assert_eq!(
+            policy.on_failure(Failure::Timeout, now),
+            Some(Action::Retry)
+        );
+    }
+
+    #[test]
+    fn blames_panics_on_gpu_only_when_diagnosed() {
+        assert_eq!(
+            panic_failure(Failure::DeviceLost),
+            Some(Failure::DeviceLost)
+        );
+        assert_eq!(panic_failure(Failure::Lost), Some(Failure::Lost));
+        assert_eq!(panic_failure(Failure::Timeout), None);
+        assert_eq!(panic_failure(Failure::Outdated), None);
+    }
+}
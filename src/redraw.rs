@@ -0,0 +1,389 @@
+//! Decide when the view renders a new frame.
+//!
+//! The `CAMetalLayer` of the view keeps showing the last frame it was given, so the view only
+//! renders when something visible changed: the primitive of the widgets, the background color
+//! or the geometry of the view. Messages handled by the application and explicit requests
+//! made with [`request`] always render a frame, e.g. for content the widgets can't describe.
+//!
+//! The primitive is only fingerprinted when the widgets produce a new one. Meshes aren't
+//! hashed, as they can be large: a new primitive drawing a mesh outside a cache always renders.
+//!
+//! [`request`]: fn.request.html
+
+#[cfg(any(target_os = "macos", test))]
+use std::collections::hash_map::DefaultHasher;
+#[cfg(any(target_os = "macos", test))]
+use std::hash::{Hash, Hasher};
+#[cfg(any(target_os = "macos", test))]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(target_os = "macos", test))]
+use std::sync::Arc;
+
+#[cfg(any(target_os = "macos", test))]
+use iced_wgpu::Primitive;
+
+use crate::{command, Command};
+#[cfg(any(target_os = "macos", test))]
+use crate::{Background, Color, Font, Geometry, Rectangle};
+
+/// Creates a `Command` making the view render a new frame, even if nothing visible changed.
+pub fn request<T>() -> Command<T> {
+    command::Action::Redraw.into()
+}
+
+// Distinguishes the primitives which aren't hashed.
+#[cfg(any(target_os = "macos", test))]
+static UNIQUE: AtomicU64 = AtomicU64::new(0);
+
+/// What a frame shows.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Frame {
+    pub(crate) primitive: Fingerprint,
+    pub(crate) background_color: Color,
+    pub(crate) geometry: Geometry,
+}
+
+/// Identifies what a primitive draws.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug, Clone)]
+pub(crate) struct Fingerprint {
+    hash: u64,
+    // Caches are identified by address. Holding them keeps a new cache from being allocated at
+    // the address of one of them while the fingerprint is in use.
+    caches: Vec<Arc<Primitive>>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl Fingerprint {
+    /// Returns the fingerprint of a primitive.
+    ///
+    /// A primitive drawing a mesh outside a cache gets a fingerprint of its own, different from
+    /// any other.
+    pub(crate) fn new(primitive: &Primitive) -> Self {
+        let mut hasher = DefaultHasher::new();
+        let mut caches = Vec::new();
+        let is_hashed = hash_primitive(primitive, &mut hasher, &mut caches);
+
+        let hash = if is_hashed {
+            hasher.finish()
+        } else {
+            let mut hasher = DefaultHasher::new();
+            UNIQUE.fetch_add(1, Ordering::Relaxed).hash(&mut hasher);
+            hasher.finish()
+        };
+
+        Self { hash, caches }
+    }
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl PartialEq for Fingerprint {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+/// Remembers what the layer shows, and whether a new frame was requested.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug, Default)]
+pub(crate) struct Tracker {
+    presented: Option<Frame>,
+    is_requested: bool,
+    // The fingerprint of the current primitive of the widgets, once computed.
+    primitive: Option<Fingerprint>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl Tracker {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the next frame render, even if it shows the same thing as the last one.
+    pub(crate) fn request(&mut self) {
+        self.is_requested = true;
+    }
+
+    /// Forgets the fingerprint of the primitive, after the widgets produced a new one.
+    pub(crate) fn invalidate(&mut self) {
+        self.primitive = None;
+    }
+
+    /// Returns what the next frame shows.
+    ///
+    /// The primitive is only fingerprinted after `invalidate` is called, and it's assumed to be
+    /// the same otherwise.
+    pub(crate) fn next_frame(
+        &mut self,
+        primitive: &Primitive,
+        background_color: Color,
+        geometry: Geometry,
+    ) -> Frame {
+        let primitive = self
+            .primitive
+            .get_or_insert_with(|| Fingerprint::new(primitive))
+            .clone();
+
+        Frame {
+            primitive,
+            background_color,
+            geometry,
+        }
+    }
+
+    /// Returns `true` if the frame has to be rendered.
+    pub(crate) fn should_redraw(&self, frame: &Frame) -> bool {
+        self.is_requested || self.presented.as_ref() != Some(frame)
+    }
+
+    /// Records the frame the layer shows from now on.
+    pub(crate) fn presented(&mut self, frame: Frame) {
+        self.presented = Some(frame);
+        self.is_requested = false;
+    }
+}
+
+/// Hashes everything the primitive draws, and collects its caches.
+///
+/// Returns `false` if the primitive draws a mesh outside a cache, which isn't hashed.
+#[cfg(any(target_os = "macos", test))]
+fn hash_primitive(
+    primitive: &Primitive,
+    state: &mut impl Hasher,
+    caches: &mut Vec<Arc<Primitive>>,
+) -> bool {
+    std::mem::discriminant(primitive).hash(state);
+
+    match primitive {
+        Primitive::None => {}
+        Primitive::Group { primitives } => {
+            primitives.len().hash(state);
+            let mut is_hashed = true;
+            for primitive in primitives {
+                is_hashed &= hash_primitive(primitive, state, caches);
+            }
+            return is_hashed;
+        }
+        Primitive::Text {
+            content,
+            bounds,
+            color,
+            size,
+            font,
+            horizontal_alignment,
+            vertical_alignment,
+        } => {
+            content.hash(state);
+            hash_rectangle(bounds, state);
+            hash_color(*color, state);
+            size.to_bits().hash(state);
+            match font {
+                Font::Default => 0u8.hash(state),
+                Font::External { name, bytes } => {
+                    name.hash(state);
+                    (bytes.as_ptr() as usize).hash(state);
+                }
+            }
+            (*horizontal_alignment as u8).hash(state);
+            (*vertical_alignment as u8).hash(state);
+        }
+        Primitive::Quad {
+            bounds,
+            background,
+            border_radius,
+            border_width,
+            border_color,
+        } => {
+            hash_rectangle(bounds, state);
+            let Background::Color(color) = background;
+            hash_color(*color, state);
+            border_radius.hash(state);
+            border_width.hash(state);
+            hash_color(*border_color, state);
+        }
+        Primitive::Image { handle, bounds } => {
+            handle.id().hash(state);
+            hash_rectangle(bounds, state);
+        }
+        Primitive::Svg { handle, bounds } => {
+            handle.id().hash(state);
+            hash_rectangle(bounds, state);
+        }
+        Primitive::Clip {
+            bounds,
+            offset,
+            content,
+        } => {
+            hash_rectangle(bounds, state);
+            offset.x.hash(state);
+            offset.y.hash(state);
+            return hash_primitive(content, state, caches);
+        }
+        Primitive::Translate {
+            translation,
+            content,
+        } => {
+            translation.x.to_bits().hash(state);
+            translation.y.to_bits().hash(state);
+            return hash_primitive(content, state, caches);
+        }
+        // Hashing the buffers would cost about as much as drawing them.
+        Primitive::Mesh2D { .. } => return false,
+        Primitive::Cached { cache } => {
+            (Arc::as_ptr(cache) as usize).hash(state);
+            caches.push(Arc::clone(cache));
+        }
+    }
+
+    true
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn hash_rectangle(rectangle: &Rectangle, state: &mut impl Hasher) {
+    for value in &[rectangle.x, rectangle.y, rectangle.width, rectangle.height] {
+        value.to_bits().hash(state);
+    }
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn hash_color(color: Color, state: &mut impl Hasher) {
+    for value in &[color.r, color.g, color.b, color.a] {
+        value.to_bits().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use iced_wgpu::triangle::{Mesh2D, Vertex2D};
+
+    use crate::Size;
+
+    fn quad(x: f32) -> Primitive {
+        Primitive::Quad {
+            bounds: Rectangle {
+                x,
+                y: 0.0,
+                width: 100.0,
+                height: 20.0,
+            },
+            background: Background::Color(Color::BLACK),
+            border_radius: 0,
+            border_width: 0,
+            border_color: Color::TRANSPARENT,
+        }
+    }
+
+    fn mesh() -> Primitive {
+        Primitive::Mesh2D {
+            buffers: Mesh2D {
+                vertices: vec![
+                    Vertex2D {
+                        position: [0.0, 0.0],
+                        color: [1.0, 1.0, 1.0, 1.0],
+                    };
+                    3
+                ],
+                indices: vec![0, 1, 2],
+            },
+            size: Size::new(100.0, 100.0),
+        }
+    }
+
+    fn geometry() -> Geometry {
+        Geometry::new(Size::new(400.0, 300.0), 2.0)
+    }
+
+    #[test]
+    fn redraws_until_presented() {
+        let mut tracker = Tracker::new();
+        let frame = tracker.next_frame(&quad(0.0), Color::WHITE, geometry());
+        assert!(tracker.should_redraw(&frame));
+
+        tracker.presented(frame);
+
+        let frame = tracker.next_frame(&quad(0.0), Color::WHITE, geometry());
+        assert!(!tracker.should_redraw(&frame));
+    }
+
+    #[test]
+    fn redraws_on_request() {
+        let mut tracker = Tracker::new();
+        let frame = tracker.next_frame(&quad(0.0), Color::WHITE, geometry());
+        tracker.presented(frame.clone());
+
+        tracker.request();
+        assert!(tracker.should_redraw(&frame));
+
+        tracker.presented(frame.clone());
+        assert!(!tracker.should_redraw(&frame));
+    }
+
+    #[test]
+    fn redraws_changes() {
+        let mut tracker = Tracker::new();
+        let frame = tracker.next_frame(&quad(0.0), Color::WHITE, geometry());
+        tracker.presented(frame);
+
+        let frame = tracker.next_frame(&quad(0.0), Color::BLACK, geometry());
+        assert!(tracker.should_redraw(&frame));
+
+        let zoomed = geometry().with_zoom(2.0);
+        let frame = tracker.next_frame(&quad(0.0), Color::WHITE, zoomed);
+        assert!(tracker.should_redraw(&frame));
+
+        tracker.invalidate();
+        let frame = tracker.next_frame(&quad(10.0), Color::WHITE, geometry());
+        assert!(tracker.should_redraw(&frame));
+    }
+
+    #[test]
+    fn fingerprints_new_primitives_only() {
+        let mut tracker = Tracker::new();
+        let frame = tracker.next_frame(&quad(0.0), Color::WHITE, geometry());
+        tracker.presented(frame);
+
+        // The widgets didn't produce a new primitive.
+        let frame = tracker.next_frame(&quad(10.0), Color::WHITE, geometry());
+        assert!(!tracker.should_redraw(&frame));
+
+        tracker.invalidate();
+        let frame = tracker.next_frame(&quad(0.0), Color::WHITE, geometry());
+        assert!(!tracker.should_redraw(&frame));
+    }
+
+    #[test]
+    fn redraws_new_meshes() {
+        assert_ne!(Fingerprint::new(&mesh()), Fingerprint::new(&mesh()));
+
+        let mut tracker = Tracker::new();
+        let frame = tracker.next_frame(&mesh(), Color::WHITE, geometry());
+        tracker.presented(frame);
+
+        let frame = tracker.next_frame(&mesh(), Color::WHITE, geometry());
+        assert!(!tracker.should_redraw(&frame));
+
+        tracker.invalidate();
+        let frame = tracker.next_frame(&mesh(), Color::WHITE, geometry());
+        assert!(tracker.should_redraw(&frame));
+    }
+
+    #[test]
+    fn identifies_caches_by_address() {
+        let cache = Arc::new(mesh());
+        let cached = |cache: &Arc<Primitive>| Primitive::Cached {
+            cache: Arc::clone(cache),
+        };
+
+        let fingerprint = Fingerprint::new(&cached(&cache));
+        assert_eq!(fingerprint, Fingerprint::new(&cached(&cache)));
+        assert_ne!(fingerprint, Fingerprint::new(&cached(&Arc::new(mesh()))));
+
+        // The fingerprint keeps the cache alive, so its address can't be reused.
+        assert_eq!(Arc::strong_count(&cache), 2);
+        drop(fingerprint);
+        assert_eq!(Arc::strong_count(&cache), 1);
+    }
+}
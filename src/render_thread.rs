@@ -0,0 +1,479 @@
+//! Render the view on a thread of its own.
+//!
+//! With `Settings::render_thread`, the main thread still handles events and runs `update` and
+//! `view`, but only hands the resulting primitive over to a render thread, which draws it into
+//! the `CAMetalLayer` of the view. A heavy frame then doesn't stall the user interface of the
+//! host.
+//!
+//! The handoff is double-buffered: the render thread owns the frame it draws, and the main
+//! thread replaces the pending one. Frames the render thread couldn't keep up with are skipped,
+//! and neither thread waits for the other's work.
+//!
+//! The render thread recovers from failures to render on its own, trying the last frame again
+//! until it renders or a newer one replaces it. It wakes the main thread up to collect the
+//! recoveries and report them to the application, and the panics the GPU isn't to blame for,
+//! which poison the view.
+//!
+//! The render thread has a GPU device and a renderer of its own, with their pipelines and glyph
+//! cache: the contexts shared between views belong to the main thread, which still lays the
+//! widgets out with its own. Each view with a render thread costs one more device, so it's best
+//! kept for views with heavy frames.
+
+#[cfg(target_os = "macos")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+#[cfg(target_os = "macos")]
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+#[cfg(target_os = "macos")]
+use iced_wgpu::{wgpu, Primitive};
+
+#[cfg(target_os = "macos")]
+use crate::context::Context;
+#[cfg(target_os = "macos")]
+use crate::recovery::{self, Action, Policy, Recovery};
+#[cfg(target_os = "macos")]
+use crate::{main_thread, mouse, poison, Color, Error, Geometry, Object};
+
+/// What the render thread tells the main thread.
+#[cfg(target_os = "macos")]
+#[derive(Debug)]
+pub(crate) enum Report {
+    /// A frame rendered again after failing to.
+    Recovery(Recovery),
+    /// Drawing a frame panicked, and not because of the GPU.
+    Panic(poison::Panic),
+}
+
+/// A frame to render.
+#[cfg(target_os = "macos")]
+#[derive(Debug)]
+pub(crate) struct Job {
+    pub(crate) primitive: Primitive,
+    pub(crate) background_color: Color,
+    pub(crate) geometry: Geometry,
+}
+
+/// The thread rendering a view, stopped when it's dropped.
+#[derive(Debug)]
+#[cfg(target_os = "macos")]
+pub(crate) struct RenderThread {
+    handoff: Arc<Handoff<Job>>,
+    reports: Arc<Mutex<Vec<Report>>>,
+    // Whether the work the render thread sends to the main thread may still run.
+    is_alive: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[cfg(target_os = "macos")]
+impl RenderThread {
+    /// Starts rendering to `surface`, the surface of `layer`, with a context of its own.
+    ///
+    /// `wake` is called on the main thread when reports are ready to be collected, until the
+    /// render thread is dropped. The layer must outlive the render thread.
+    pub(crate) fn spawn(
+        layer: *mut Object,
+        surface: wgpu::Surface,
+        context: Context,
+        format: wgpu::TextureFormat,
+        present_mode: wgpu::PresentMode,
+        wake: impl Fn() + Send + Sync + 'static,
+    ) -> Result<Self, Error> {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let is_alive = Arc::new(AtomicBool::new(true));
+        let mut target = Target {
+            layer: layer as usize,
+            context,
+            surface,
+            format,
+            present_mode,
+            swap_chain: None,
+            policy: Policy::new(),
+            reports: Arc::clone(&reports),
+            is_alive: Arc::clone(&is_alive),
+            wake: Arc::new(wake),
+        };
+        let handoff = Arc::new(Handoff::new());
+        let jobs = Arc::clone(&handoff);
+        let thread = thread::Builder::new()
+            .name(String::from("iced-nsview render"))
+            .spawn(move || {
+                let mut retry = None;
+                loop {
+                    let job = match retry.take() {
+                        Some(job) => match jobs.take_until(target.retry_at()) {
+                            // The newer frame waits for the next attempt instead.
+                            Taken::Value(newer) => {
+                                retry = Some(newer);
+                                continue;
+                            }
+                            Taken::TimedOut => job,
+                            Taken::Closed => break,
+                        },
+                        None => match jobs.take() {
+                            Some(job) => job,
+                            None => break,
+                        },
+                    };
+
+                    retry = target.render(job);
+                }
+            })
+            .map_err(|_| Error::ThreadSpawnFailed)?;
+
+        Ok(Self {
+            handoff,
+            reports,
+            is_alive,
+            thread: Some(thread),
+        })
+    }
+
+    /// Renders the frame as soon as the render thread is free, unless a newer one replaces it.
+    pub(crate) fn submit(&self, job: Job) {
+        self.handoff.submit(job);
+    }
+
+    /// Returns the reports of the render thread since the last call.
+    pub(crate) fn take_reports(&self) -> Vec<Report> {
+        let mut reports = self
+            .reports
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+
+        std::mem::take(&mut *reports)
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        // The work already sent to the main thread runs after the view is gone.
+        self.is_alive.store(false, Ordering::SeqCst);
+        self.handoff.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// What the render thread draws into.
+#[cfg(target_os = "macos")]
+struct Target {
+    // The `CAMetalLayer` of the view.
+    layer: usize,
+    context: Context,
+    surface: wgpu::Surface,
+    format: wgpu::TextureFormat,
+    present_mode: wgpu::PresentMode,
+    // With the geometry it was created for.
+    swap_chain: Option<(wgpu::SwapChain, Geometry)>,
+    policy: Policy,
+    reports: Arc<Mutex<Vec<Report>>>,
+    is_alive: Arc<AtomicBool>,
+    wake: Arc<dyn Fn() + Send + Sync>,
+}
+
+#[cfg(target_os = "macos")]
+impl Target {
+    /// Renders the job, and returns it if it has to be tried again.
+    fn render(&mut self, job: Job) -> Option<Job> {
+        let is_outdated = match &self.swap_chain {
+            Some((_, geometry)) => geometry.physical_size() != job.geometry.physical_size(),
+            None => true,
+        };
+        if is_outdated {
+            self.recreate_swap_chain(job.geometry);
+        }
+
+        let frame = match &mut self.swap_chain {
+            Some((swap_chain, _)) => swap_chain.get_next_texture(),
+            None => return None,
+        };
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(_) => {
+                let size = job.geometry.physical_size();
+                let failure = unsafe { recovery::diagnose(self.layer as *mut Object, size) };
+                return self.on_failure(failure, job);
+            }
+        };
+
+        let viewport = job.geometry.viewport();
+        let background_color = job.background_color;
+        let primitive = (job.primitive, mouse::Interaction::Idle);
+        let context = &mut self.context;
+        // wgpu panics when the device fails while drawing.
+        let drawn = poison::catch(|| {
+            context.draw(
+                &frame,
+                &viewport,
+                background_color,
+                &primitive,
+                &[] as &[String],
+            )
+        });
+
+        let job = Job {
+            primitive: primitive.0,
+            ..job
+        };
+        match drawn {
+            Ok(()) => {
+                if let Some(recovery) = self.policy.on_success() {
+                    self.report(Report::Recovery(recovery));
+                }
+                None
+            }
+            Err(panic) => {
+                let size = job.geometry.physical_size();
+                let diagnosis = unsafe { recovery::diagnose(self.layer as *mut Object, size) };
+                match recovery::panic_failure(diagnosis) {
+                    Some(failure) => self.on_failure(failure, job),
+                    None => {
+                        self.report(Report::Panic(panic));
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Hands the report over to the main thread, and wakes it up unless it's already due to
+    /// collect the reports.
+    fn report(&self, report: Report) {
+        let mut reports = self
+            .reports
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        reports.push(report);
+        if reports.len() > 1 {
+            return;
+        }
+        drop(reports);
+
+        let is_alive = Arc::clone(&self.is_alive);
+        let wake = Arc::clone(&self.wake);
+        main_thread::run(move |_| {
+            // Both the check and the drop of the render thread happen on the main thread.
+            if is_alive.load(Ordering::SeqCst) {
+                wake();
+            }
+        });
+    }
+
+    /// Returns when the frame which failed can be tried again.
+    fn retry_at(&self) -> Instant {
+        self.policy.retry_at().unwrap_or_else(Instant::now)
+    }
+
+    /// Responds to the failure to render the job, and returns the job if it has to be tried
+    /// again.
+    fn on_failure(&mut self, failure: recovery::Failure, job: Job) -> Option<Job> {
+        match self.policy.on_failure(failure, Instant::now()) {
+            Some(Action::Retry) => {}
+            Some(Action::RecreateSwapChain) => self.recreate_swap_chain(job.geometry),
+            Some(Action::RecreateDevice) => {
+                // On error, the next failure tries again.
+                if self.context.recreate(&self.surface).is_ok() {
+                    self.recreate_swap_chain(job.geometry);
+                }
+            }
+            // The next job the main thread submits tries again.
+            None => return None,
+        }
+
+        Some(job)
+    }
+
+    fn recreate_swap_chain(&mut self, geometry: Geometry) {
+        self.swap_chain = self
+            .context
+            .create_swap_chain(&self.surface, &geometry, self.format, self.present_mode)
+            .map(|swap_chain| (swap_chain, geometry));
+    }
+}
+
+/// What waiting for a value ended with.
+#[derive(Debug)]
+pub(crate) enum Taken<T> {
+    Value(T),
+    TimedOut,
+    Closed,
+}
+
+/// Passes values from one thread to another, keeping only the latest one.
+#[derive(Debug)]
+pub(crate) struct Handoff<T> {
+    slot: Mutex<Slot<T>>,
+    ready: Condvar,
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    pending: Option<T>,
+    is_closed: bool,
+}
+
+impl<T> Handoff<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            slot: Mutex::new(Slot {
+                pending: None,
+                is_closed: false,
+            }),
+            ready: Condvar::new(),
+        }
+    }
+
+    /// Makes `value` the next one taken, and returns the pending value it replaces, if any.
+    pub(crate) fn submit(&self, value: T) -> Option<T> {
+        let replaced = self.lock().pending.replace(value);
+        self.ready.notify_one();
+
+        replaced
+    }
+
+    /// Waits for a value and takes it, or returns `None` once the handoff is closed.
+    pub(crate) fn take(&self) -> Option<T> {
+        let mut slot = self.lock();
+        loop {
+            if slot.is_closed {
+                return None;
+            }
+            if let Some(value) = slot.pending.take() {
+                return Some(value);
+            }
+            slot = self
+                .ready
+                .wait(slot)
+                .unwrap_or_else(|error| error.into_inner());
+        }
+    }
+
+    /// Waits for a value until `deadline`, and takes it.
+    pub(crate) fn take_until(&self, deadline: Instant) -> Taken<T> {
+        let mut slot = self.lock();
+        loop {
+            if slot.is_closed {
+                return Taken::Closed;
+            }
+            if let Some(value) = slot.pending.take() {
+                return Taken::Value(value);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Taken::TimedOut;
+            }
+            slot = match self.ready.wait_timeout(slot, deadline - now) {
+                Ok((slot, _)) => slot,
+                Err(error) => error.into_inner().0,
+            };
+        }
+    }
+
+    /// Wakes the taking threads up for good. The pending value is dropped.
+    pub(crate) fn close(&self) {
+        let mut slot = self.lock();
+        slot.is_closed = true;
+        slot.pending = None;
+        drop(slot);
+
+        self.ready.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Slot<T>> {
+        // A panic while rendering leaves a consistent slot behind.
+        self.slot.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn submit_replaces_pending_value() {
+        let handoff = Handoff::new();
+
+        assert_eq!(handoff.submit(1), None);
+        assert_eq!(handoff.submit(2), Some(1));
+
+        assert_eq!(handoff.take(), Some(2));
+        assert_eq!(handoff.submit(3), None);
+    }
+
+    #[test]
+    fn take_until_times_out() {
+        let handoff = Handoff::<u32>::new();
+        let deadline = Instant::now() + Duration::from_millis(20);
+
+        assert!(matches!(handoff.take_until(deadline), Taken::TimedOut));
+        assert!(Instant::now() >= deadline);
+    }
+
+    #[test]
+    fn take_until_takes_pending_value() {
+        let handoff = Handoff::new();
+        handoff.submit(1);
+
+        // Even past the deadline.
+        assert!(matches!(
+            handoff.take_until(Instant::now()),
+            Taken::Value(1)
+        ));
+    }
+
+    #[test]
+    fn taker_waits_for_submitted_value() {
+        let handoff = Arc::new(Handoff::new());
+        let taker = {
+            let handoff = Arc::clone(&handoff);
+            thread::spawn(move || handoff.take())
+        };
+
+        thread::sleep(Duration::from_millis(20));
+        handoff.submit(1);
+
+        assert_eq!(taker.join().unwrap(), Some(1));
+    }
+
+    #[test]
+    fn close_wakes_taker() {
+        let handoff = Arc::new(Handoff::<u32>::new());
+        let takers = vec![
+            {
+                let handoff = Arc::clone(&handoff);
+                thread::spawn(move || handoff.take().is_none())
+            },
+            {
+                let handoff = Arc::clone(&handoff);
+                let deadline = Instant::now() + Duration::from_secs(60);
+                thread::spawn(move || matches!(handoff.take_until(deadline), Taken::Closed))
+            },
+        ];
+
+        thread::sleep(Duration::from_millis(20));
+        handoff.close();
+        handoff.close();
+
+        for taker in takers {
+            assert!(taker.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn close_drops_pending_value() {
+        let handoff = Handoff::new();
+        handoff.submit(1);
+        handoff.close();
+
+        assert_eq!(handoff.take(), None);
+        assert!(matches!(handoff.take_until(Instant::now()), Taken::Closed));
+    }
+}
@@ -0,0 +1,328 @@
+//! Negotiate the size of the view with the host.
+//!
+//! `Application::size_hints` describes the sizes the user interface supports. Hosts which
+//! propose a size before resizing the view can constrain it with `IcedView::constrain_size`,
+//! and the application can ask for another size with [`request`], e.g. from a [`ResizeGrip`].
+//!
+//! Sizes are in the coordinate system of the widgets, so they don't change with the zoom.
+//!
+//! [`request`]: fn.request.html
+//! [`ResizeGrip`]: ../widget/resize_grip/struct.ResizeGrip.html
+
+use crate::{command, Command, Size};
+
+/// The sizes supported by the user interface.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SizeHints {
+    min: Option<Size>,
+    max: Option<Size>,
+    aspect_ratio: Option<f32>,
+    step: Option<Size>,
+}
+
+impl SizeHints {
+    /// Creates hints which accept any size.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates hints which only accept the given size.
+    pub fn fixed(size: Size) -> Self {
+        Self::new().min(size).max(size)
+    }
+
+    /// Sets the minimum size.
+    ///
+    /// Dimensions which aren't positive and finite are treated as zero.
+    pub fn min(mut self, size: Size) -> Self {
+        self.min = Some(Size::new(
+            finite_or(size.width, 0.0),
+            finite_or(size.height, 0.0),
+        ));
+        self
+    }
+
+    /// Sets the maximum size.
+    ///
+    /// Infinite dimensions are unbounded, and `NaN` ones are treated as such.
+    pub fn max(mut self, size: Size) -> Self {
+        let bound = |value: f32| {
+            if value.is_nan() {
+                f32::INFINITY
+            } else {
+                value.max(0.0)
+            }
+        };
+
+        self.max = Some(Size::new(bound(size.width), bound(size.height)));
+        self
+    }
+
+    /// Sets the ratio of the width to the height to keep, e.g. `4.0 / 3.0`.
+    pub fn aspect_ratio(mut self, ratio: f32) -> Self {
+        self.aspect_ratio = Some(ratio).filter(|ratio| ratio.is_finite() && *ratio > 0.0);
+        self
+    }
+
+    /// Sets the increments in which the size grows from the minimum size.
+    ///
+    /// With an aspect ratio, only the width follows its step, or the height if the step of the
+    /// width isn't positive, and the other dimension keeps the ratio.
+    pub fn step(mut self, step: Size) -> Self {
+        self.step = Some(step);
+        self
+    }
+
+    /// Returns `true` if only one size is accepted.
+    pub fn is_fixed(&self) -> bool {
+        self.min.is_some() && self.min == self.max
+    }
+
+    /// Returns the supported size closest to the proposed one.
+    ///
+    /// The size is clamped between the minimum and the maximum, then shrunk to the aspect ratio
+    /// and rounded down to the step. If the hints contradict each other, the minimum size wins.
+    ///
+    /// A proposed dimension which is `NaN` or negative is treated as zero, and an infinite one
+    /// as the maximum, or the minimum if there's no maximum, so the size is always finite.
+    pub fn constrain(&self, proposed: Size) -> Size {
+        let min = self.min.unwrap_or(Size::ZERO);
+        let max = self.max.unwrap_or(Size::INFINITY);
+        let clamp = |size: Size| {
+            Size::new(
+                size.width.min(max.width).max(min.width),
+                size.height.min(max.height).max(min.height),
+            )
+        };
+
+        let size = clamp(Size::new(
+            if proposed.width.is_nan() {
+                0.0
+            } else {
+                proposed.width
+            },
+            if proposed.height.is_nan() {
+                0.0
+            } else {
+                proposed.height
+            },
+        ));
+        let size = Size::new(
+            finite_or(size.width, min.width),
+            finite_or(size.height, min.height),
+        );
+
+        let size = match self.aspect_ratio {
+            Some(ratio) => {
+                let shrunk = if size.width > size.height * ratio {
+                    Size::new(size.height * ratio, size.height)
+                } else {
+                    Size::new(size.width, size.width / ratio)
+                };
+
+                // Growing back to the minimum keeps the ratio as long as the maximum allows it.
+                if shrunk.width < min.width {
+                    clamp(Size::new(min.width, min.width / ratio))
+                } else if shrunk.height < min.height {
+                    clamp(Size::new(min.height * ratio, min.height))
+                } else {
+                    shrunk
+                }
+            }
+            None => size,
+        };
+
+        match (self.step, self.aspect_ratio) {
+            // Stepping both dimensions would break the ratio, so one follows the other.
+            (Some(step), Some(ratio)) if is_step(step.width) => {
+                let mut width = round_down(size.width, min.width, step.width);
+                if width / ratio < min.height {
+                    width = round_up(min.height * ratio, min.width, step.width);
+                }
+
+                Size::new(width, width / ratio)
+            }
+            (Some(step), Some(ratio)) if is_step(step.height) => {
+                let mut height = round_down(size.height, min.height, step.height);
+                if height * ratio < min.width {
+                    height = round_up(min.width / ratio, min.height, step.height);
+                }
+
+                Size::new(height * ratio, height)
+            }
+            (Some(step), _) => Size::new(
+                round_down(size.width, min.width, step.width),
+                round_down(size.height, min.height, step.height),
+            ),
+            (None, _) => size,
+        }
+    }
+}
+
+/// Creates a command which asks the host to resize the view to the given size.
+///
+/// The size is constrained by `Application::size_hints` first. The host may still refuse it, in
+/// which case the view keeps its size.
+pub fn request<T>(size: Size) -> Command<T> {
+    command::Action::Resize(size).into()
+}
+
+fn finite_or(value: f32, default: f32) -> f32 {
+    if value.is_finite() && value > 0.0 {
+        value
+    } else {
+        default
+    }
+}
+
+fn is_step(step: f32) -> bool {
+    step.is_finite() && step > 0.0
+}
+
+fn round_down(value: f32, base: f32, step: f32) -> f32 {
+    if !is_step(step) || value <= base {
+        return value;
+    }
+
+    base + ((value - base) / step).floor() * step
+}
+
+fn round_up(value: f32, base: f32, step: f32) -> f32 {
+    if !is_step(step) || value <= base {
+        return base.max(value);
+    }
+
+    base + ((value - base) / step).ceil() * step
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_any_size_by_default() {
+        let hints = SizeHints::new();
+
+        assert!(!hints.is_fixed());
+        assert_eq!(
+            hints.constrain(Size::new(300.0, 200.0)),
+            Size::new(300.0, 200.0)
+        );
+    }
+
+    #[test]
+    fn clamps_between_min_and_max() {
+        let hints = SizeHints::new()
+            .min(Size::new(100.0, 100.0))
+            .max(Size::new(400.0, 300.0));
+
+        assert_eq!(
+            hints.constrain(Size::new(500.0, 50.0)),
+            Size::new(400.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn sanitizes_proposed_size() {
+        let hints = SizeHints::new().min(Size::new(100.0, 50.0));
+        assert_eq!(
+            hints.constrain(Size::new(f32::NAN, f32::INFINITY)),
+            Size::new(100.0, 50.0)
+        );
+
+        let hints = hints.max(Size::new(400.0, 300.0));
+        assert_eq!(
+            hints.constrain(Size::new(f32::INFINITY, -10.0)),
+            Size::new(400.0, 50.0)
+        );
+        assert_eq!(
+            SizeHints::new().constrain(Size::new(f32::NEG_INFINITY, f32::NAN)),
+            Size::ZERO
+        );
+    }
+
+    #[test]
+    fn sanitizes_hints() {
+        let hints = SizeHints::new()
+            .min(Size::new(f32::NAN, -5.0))
+            .max(Size::new(f32::NAN, 100.0))
+            .aspect_ratio(f32::NAN)
+            .aspect_ratio(0.0);
+
+        assert_eq!(hints.constrain(Size::ZERO), Size::ZERO);
+        assert_eq!(
+            hints.constrain(Size::new(1000.0, 1000.0)),
+            Size::new(1000.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn shrinks_to_aspect_ratio() {
+        let hints = SizeHints::new().aspect_ratio(2.0);
+
+        assert_eq!(
+            hints.constrain(Size::new(500.0, 200.0)),
+            Size::new(400.0, 200.0)
+        );
+        assert_eq!(
+            hints.constrain(Size::new(300.0, 200.0)),
+            Size::new(300.0, 150.0)
+        );
+    }
+
+    #[test]
+    fn rounds_down_to_step() {
+        let hints = SizeHints::new()
+            .min(Size::new(100.0, 100.0))
+            .step(Size::new(10.0, 25.0));
+
+        assert_eq!(
+            hints.constrain(Size::new(157.0, 180.0)),
+            Size::new(150.0, 175.0)
+        );
+    }
+
+    #[test]
+    fn step_keeps_aspect_ratio() {
+        let hints = SizeHints::new()
+            .min(Size::new(100.0, 50.0))
+            .aspect_ratio(2.0)
+            .step(Size::new(30.0, 7.0));
+
+        // Only the width follows its step.
+        assert_eq!(
+            hints.constrain(Size::new(500.0, 300.0)),
+            Size::new(490.0, 245.0)
+        );
+
+        let hints = hints.step(Size::new(0.0, 20.0));
+
+        assert_eq!(
+            hints.constrain(Size::new(500.0, 240.0)),
+            Size::new(460.0, 230.0)
+        );
+    }
+
+    #[test]
+    fn min_wins_contradictions() {
+        let hints = SizeHints::new()
+            .min(Size::new(300.0, 300.0))
+            .max(Size::new(200.0, 200.0));
+
+        assert_eq!(
+            hints.constrain(Size::new(250.0, 250.0)),
+            Size::new(300.0, 300.0)
+        );
+    }
+
+    #[test]
+    fn fixed_accepts_one_size() {
+        let hints = SizeHints::fixed(Size::new(320.0, 240.0));
+
+        assert!(hints.is_fixed());
+        assert_eq!(
+            hints.constrain(Size::new(1.0, 1.0)),
+            Size::new(320.0, 240.0)
+        );
+    }
+}
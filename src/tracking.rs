@@ -0,0 +1,55 @@
+//! Configure when the view tracks the mouse and how it treats clicks activating its window.
+
+/// When the view receives mouse moves and enter/exit events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activation {
+    /// Always, even when the application is in the background.
+    Always,
+    /// While the application is active, even if the window of the view isn't the key window.
+    ///
+    /// This is what plugin windows usually need, as hosts rarely make them key.
+    ActiveApp,
+    /// Only while the window of the view is the key window.
+    KeyWindow,
+}
+
+impl Activation {
+    /// Returns the `NSTrackingAreaOptions` of the tracking area installed on the view.
+    #[cfg(target_os = "macos")]
+    pub(crate) fn options(self) -> u64 {
+        // NSTrackingMouseEnteredAndExited | NSTrackingMouseMoved | NSTrackingCursorUpdate |
+        // NSTrackingInVisibleRect
+        let events = 0x01 | 0x02 | 0x04 | 0x200;
+        let activation = match self {
+            // NSTrackingActiveAlways
+            Activation::Always => 0x80,
+            // NSTrackingActiveInActiveApp
+            Activation::ActiveApp => 0x40,
+            // NSTrackingActiveInKeyWindow
+            Activation::KeyWindow => 0x20,
+        };
+
+        events | activation
+    }
+}
+
+impl Default for Activation {
+    fn default() -> Self {
+        Activation::KeyWindow
+    }
+}
+
+/// What happens to a click which activates the window of the view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirstMouse {
+    /// The click only activates the window, as in most AppKit views.
+    Ignore,
+    /// The click activates the window and is also handled by the view.
+    Accept,
+}
+
+impl Default for FirstMouse {
+    fn default() -> Self {
+        FirstMouse::Ignore
+    }
+}
@@ -17,6 +17,9 @@
 //! [`TextInput`]: text_input/struct.TextInput.html
 //! [`text_input::State`]: text_input/struct.State.html
 
+pub mod dnd;
+pub mod resize_grip;
+
 pub mod image {
     //! Display images in your user interface.
     pub use iced_native::image::{Handle, Image};
@@ -35,6 +38,6 @@ pub use iced_wgpu::{
 #[doc(no_inline)]
 pub use {
     button::Button, canvas::Canvas, checkbox::Checkbox, container::Container, image::Image,
-    pane_grid::PaneGrid, progress_bar::ProgressBar, radio::Radio, scrollable::Scrollable,
-    slider::Slider, svg::Svg, text_input::TextInput,
+    pane_grid::PaneGrid, progress_bar::ProgressBar, radio::Radio, resize_grip::ResizeGrip,
+    scrollable::Scrollable, slider::Slider, svg::Svg, text_input::TextInput,
 };
@@ -0,0 +1,811 @@
+//! Drag elements onto other elements of the same view.
+//!
+//! Wrap the dragged elements in a [`Source`], the elements accepting them in a [`Target`], and
+//! the part of the interface containing both in an [`Area`]. All of them share a [`State`],
+//! which is kept in your application:
+//!
+//! ```
+//! use iced_nsview::dnd::{self, Area, Source, Target};
+//! use iced_nsview::{Column, Element, Text};
+//!
+//! #[derive(Debug, Clone)]
+//! enum Message {
+//!     Moved { slot: usize, to: usize },
+//! }
+//!
+//! fn view(state: &dnd::State<usize, usize>) -> Element<'_, Message> {
+//!     let slots = (0..4).fold(Column::new(), |column, slot| {
+//!         column.push(Target::new(
+//!             state,
+//!             slot,
+//!             Source::new(state, slot, slot, Text::new(format!("Slot {}", slot))),
+//!         ))
+//!     });
+//!
+//!     Area::new(state, slots)
+//!         .on_drop(|slot, to| Message::Moved { slot, to })
+//!         .into()
+//! }
+//! ```
+//!
+//! The dragged element is drawn under the cursor on top of everything else in the [`Area`].
+//!
+//! [`Source`]: struct.Source.html
+//! [`Target`]: struct.Target.html
+//! [`Area`]: struct.Area.html
+//! [`State`]: struct.State.html
+
+use std::cell::RefCell;
+use std::hash::Hash;
+
+use iced_native::{layout, mouse, Clipboard, Event, Hasher, Layout, Widget};
+use iced_wgpu::{Defaults, Primitive};
+
+use crate::drag::THRESHOLD;
+use crate::{Background, Color, Element, Length, Point, Rectangle, Renderer, Vector};
+
+/// The drag and drop state shared by an [`Area`] and its sources and targets.
+///
+/// `P` is the dragged payload and `K` identifies the sources and the targets.
+///
+/// [`Area`]: struct.Area.html
+#[derive(Debug)]
+pub struct State<P, K> {
+    session: RefCell<Session<P, K>>,
+}
+
+impl<P: Clone, K: Clone + PartialEq> State<P, K> {
+    /// Creates an idle state.
+    pub fn new() -> Self {
+        Self {
+            session: RefCell::new(Session::new()),
+        }
+    }
+
+    /// Returns the payload being dragged, if any.
+    pub fn payload(&self) -> Option<P> {
+        self.session.borrow().payload().cloned()
+    }
+
+    /// Returns the target under the cursor which accepts the payload being dragged.
+    pub fn hovered(&self) -> Option<K> {
+        self.session.borrow().hovered.clone()
+    }
+
+    /// Returns `true` if a payload is being dragged.
+    pub fn is_dragging(&self) -> bool {
+        self.session.borrow().payload().is_some()
+    }
+}
+
+impl<P: Clone, K: Clone + PartialEq> Default for State<P, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+enum Phase<P, K> {
+    Idle,
+    Pressed {
+        origin: Point,
+        source: K,
+        payload: P,
+    },
+    Dragging {
+        origin: Point,
+        source: K,
+        payload: P,
+    },
+}
+
+/// What happened to the drag after an event went through the area.
+#[derive(Debug, PartialEq)]
+enum Transition<P, K> {
+    Started(P),
+    Hovered(Option<K>),
+    Dropped(P, K),
+    Cancelled(P),
+}
+
+#[derive(Debug)]
+struct Session<P, K> {
+    phase: Phase<P, K>,
+    cursor: Point,
+    hovered: Option<K>,
+    candidate: Option<K>,
+    preview: Option<Primitive>,
+}
+
+impl<P: Clone, K: Clone + PartialEq> Session<P, K> {
+    fn new() -> Self {
+        Self {
+            phase: Phase::Idle,
+            cursor: Point::ORIGIN,
+            hovered: None,
+            candidate: None,
+            preview: None,
+        }
+    }
+
+    fn payload(&self) -> Option<&P> {
+        match &self.phase {
+            Phase::Dragging { payload, .. } => Some(payload),
+            _ => None,
+        }
+    }
+
+    fn is_dragged(&self, key: &K) -> bool {
+        match &self.phase {
+            Phase::Dragging { source, .. } => source == key,
+            _ => false,
+        }
+    }
+
+    /// Records the press of the mouse button over a source.
+    ///
+    /// The innermost source gets the press, as it handles the event first.
+    fn press(&mut self, source: &K, payload: &P) {
+        if let Phase::Idle = self.phase {
+            self.phase = Phase::Pressed {
+                origin: self.cursor,
+                source: source.clone(),
+                payload: payload.clone(),
+            };
+        }
+    }
+
+    /// Claims the target under the cursor.
+    ///
+    /// The innermost target gets the claim, as it handles the event first.
+    fn claim(&mut self, target: &K, bounds: Rectangle, accept: impl FnOnce(&P) -> bool) {
+        let accepted = match (&self.phase, &self.candidate) {
+            (Phase::Dragging { payload, .. }, None) => {
+                bounds.contains(self.cursor) && accept(payload)
+            }
+            _ => false,
+        };
+
+        if accepted {
+            self.candidate = Some(target.clone());
+        }
+    }
+
+    /// Handles an event before the content of the area does.
+    fn before(&mut self, event: &Event) -> Option<Transition<P, K>> {
+        match event {
+            Event::Mouse(mouse::Event::CursorMoved { x, y }) => {
+                self.cursor = Point::new(*x, *y);
+                self.candidate = None;
+
+                let (origin, source, payload) = match &self.phase {
+                    Phase::Pressed {
+                        origin,
+                        source,
+                        payload,
+                    } if distance(self.cursor, *origin) > THRESHOLD => {
+                        (*origin, source.clone(), payload.clone())
+                    }
+                    _ => return None,
+                };
+                self.phase = Phase::Dragging {
+                    origin,
+                    source,
+                    payload: payload.clone(),
+                };
+
+                Some(Transition::Started(payload))
+            }
+            _ => None,
+        }
+    }
+
+    /// Handles an event after the content of the area did.
+    fn after(&mut self, event: &Event) -> Option<Transition<P, K>> {
+        match event {
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                if self.payload().is_none() || self.candidate == self.hovered {
+                    return None;
+                }
+                self.hovered = self.candidate.clone();
+
+                Some(Transition::Hovered(self.hovered.clone()))
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                let phase = std::mem::replace(&mut self.phase, Phase::Idle);
+                let hovered = self.hovered.take();
+                self.candidate = None;
+                self.preview = None;
+
+                match (phase, hovered) {
+                    (Phase::Dragging { payload, .. }, Some(target)) => {
+                        Some(Transition::Dropped(payload, target))
+                    }
+                    (Phase::Dragging { payload, .. }, None) => Some(Transition::Cancelled(payload)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the translation of the dragged source to the cursor.
+    fn translation(&self) -> Option<Vector> {
+        match &self.phase {
+            Phase::Dragging { origin, .. } => Some(Vector::new(
+                self.cursor.x - origin.x,
+                self.cursor.y - origin.y,
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+/// The part of the interface where elements are dragged.
+///
+/// It produces the drag and drop messages and draws the dragged element on top of its content.
+pub struct Area<'a, Message, P, K> {
+    state: &'a State<P, K>,
+    content: Element<'a, Message>,
+    on_start: Option<Box<dyn Fn(P) -> Message + 'a>>,
+    on_hover: Option<Box<dyn Fn(Option<K>) -> Message + 'a>>,
+    on_drop: Option<Box<dyn Fn(P, K) -> Message + 'a>>,
+    on_cancel: Option<Box<dyn Fn(P) -> Message + 'a>>,
+}
+
+impl<'a, Message, P, K> Area<'a, Message, P, K> {
+    /// Creates an area with the given content.
+    pub fn new(state: &'a State<P, K>, content: impl Into<Element<'a, Message>>) -> Self {
+        Self {
+            state,
+            content: content.into(),
+            on_start: None,
+            on_hover: None,
+            on_drop: None,
+            on_cancel: None,
+        }
+    }
+
+    /// Sets the message produced when a payload starts being dragged.
+    pub fn on_start(mut self, f: impl Fn(P) -> Message + 'a) -> Self {
+        self.on_start = Some(Box::new(f));
+        self
+    }
+
+    /// Sets the message produced when the cursor enters or leaves a target accepting the
+    /// payload.
+    pub fn on_hover(mut self, f: impl Fn(Option<K>) -> Message + 'a) -> Self {
+        self.on_hover = Some(Box::new(f));
+        self
+    }
+
+    /// Sets the message produced when the payload is dropped on a target accepting it.
+    pub fn on_drop(mut self, f: impl Fn(P, K) -> Message + 'a) -> Self {
+        self.on_drop = Some(Box::new(f));
+        self
+    }
+
+    /// Sets the message produced when the payload is released outside of any target
+    /// accepting it.
+    pub fn on_cancel(mut self, f: impl Fn(P) -> Message + 'a) -> Self {
+        self.on_cancel = Some(Box::new(f));
+        self
+    }
+
+    fn message(&self, transition: Transition<P, K>) -> Option<Message> {
+        match transition {
+            Transition::Started(payload) => self.on_start.as_ref().map(|f| f(payload)),
+            Transition::Hovered(target) => self.on_hover.as_ref().map(|f| f(target)),
+            Transition::Dropped(payload, target) => {
+                self.on_drop.as_ref().map(|f| f(payload, target))
+            }
+            Transition::Cancelled(payload) => self.on_cancel.as_ref().map(|f| f(payload)),
+        }
+    }
+}
+
+impl<'a, Message, P, K> Widget<Message, Renderer> for Area<'a, Message, P, K>
+where
+    P: Clone,
+    K: Clone + PartialEq,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        self.content.layout(renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        defaults: &Defaults,
+        layout: Layout<'_>,
+        cursor_position: Point,
+    ) -> (Primitive, mouse::Interaction) {
+        self.state.session.borrow_mut().preview = None;
+
+        let (content, interaction) = self
+            .content
+            .draw(renderer, defaults, layout, cursor_position);
+        let mut session = self.state.session.borrow_mut();
+
+        match (session.preview.take(), session.translation()) {
+            (Some(preview), Some(translation)) => (
+                Primitive::Group {
+                    primitives: vec![
+                        content,
+                        Primitive::Translate {
+                            translation,
+                            content: Box::new(preview),
+                        },
+                    ],
+                },
+                mouse::Interaction::Grabbing,
+            ),
+            _ => (content, interaction),
+        }
+    }
+
+    fn hash_layout(&self, state: &mut Hasher) {
+        struct Marker;
+        std::any::TypeId::of::<Marker>().hash(state);
+
+        self.content.hash_layout(state);
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        messages: &mut Vec<Message>,
+        renderer: &Renderer,
+        clipboard: Option<&dyn Clipboard>,
+    ) {
+        let before = self.state.session.borrow_mut().before(&event);
+        if let Some(message) = before.and_then(|transition| self.message(transition)) {
+            messages.push(message);
+        }
+
+        self.content.on_event(
+            event.clone(),
+            layout,
+            cursor_position,
+            messages,
+            renderer,
+            clipboard,
+        );
+
+        let after = self.state.session.borrow_mut().after(&event);
+        if let Some(message) = after.and_then(|transition| self.message(transition)) {
+            messages.push(message);
+        }
+    }
+}
+
+impl<'a, Message, P, K> From<Area<'a, Message, P, K>> for Element<'a, Message>
+where
+    Message: 'a,
+    P: 'a + Clone,
+    K: 'a + Clone + PartialEq,
+{
+    fn from(area: Area<'a, Message, P, K>) -> Element<'a, Message> {
+        Element::new(area)
+    }
+}
+
+/// An element which can be dragged with the left mouse button.
+pub struct Source<'a, Message, P, K> {
+    state: &'a State<P, K>,
+    key: K,
+    payload: P,
+    content: Element<'a, Message>,
+}
+
+impl<'a, Message, P, K> Source<'a, Message, P, K> {
+    /// Creates a source identified by the given key, dragging the given payload.
+    ///
+    /// The key tells which source is dragged, so it must be unique among the sources of the
+    /// area and stay the same while the interface is rebuilt.
+    pub fn new(
+        state: &'a State<P, K>,
+        key: K,
+        payload: P,
+        content: impl Into<Element<'a, Message>>,
+    ) -> Self {
+        Self {
+            state,
+            key,
+            payload,
+            content: content.into(),
+        }
+    }
+}
+
+impl<'a, Message, P, K> Widget<Message, Renderer> for Source<'a, Message, P, K>
+where
+    P: Clone,
+    K: Clone + PartialEq,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        self.content.layout(renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        defaults: &Defaults,
+        layout: Layout<'_>,
+        cursor_position: Point,
+    ) -> (Primitive, mouse::Interaction) {
+        let (content, interaction) = self
+            .content
+            .draw(renderer, defaults, layout, cursor_position);
+        let mut session = self.state.session.borrow_mut();
+
+        if session.is_dragged(&self.key) {
+            session.preview = Some(content.clone());
+            (content, mouse::Interaction::Grabbing)
+        } else {
+            (content, interaction)
+        }
+    }
+
+    fn hash_layout(&self, state: &mut Hasher) {
+        struct Marker;
+        std::any::TypeId::of::<Marker>().hash(state);
+
+        self.content.hash_layout(state);
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        messages: &mut Vec<Message>,
+        renderer: &Renderer,
+        clipboard: Option<&dyn Clipboard>,
+    ) {
+        self.content.on_event(
+            event.clone(),
+            layout,
+            cursor_position,
+            messages,
+            renderer,
+            clipboard,
+        );
+
+        if let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event {
+            if layout.bounds().contains(cursor_position) {
+                self.state
+                    .session
+                    .borrow_mut()
+                    .press(&self.key, &self.payload);
+            }
+        }
+    }
+}
+
+impl<'a, Message, P, K> From<Source<'a, Message, P, K>> for Element<'a, Message>
+where
+    Message: 'a,
+    P: 'a + Clone,
+    K: 'a + Clone + PartialEq,
+{
+    fn from(source: Source<'a, Message, P, K>) -> Element<'a, Message> {
+        Element::new(source)
+    }
+}
+
+/// An element on which payloads can be dropped.
+pub struct Target<'a, Message, P, K> {
+    state: &'a State<P, K>,
+    key: K,
+    content: Element<'a, Message>,
+    accept: Box<dyn Fn(&P) -> bool + 'a>,
+    highlight: Option<Color>,
+}
+
+impl<'a, Message, P, K> Target<'a, Message, P, K> {
+    /// Creates a target identified by the given key.
+    ///
+    /// By default, it accepts any payload.
+    pub fn new(state: &'a State<P, K>, key: K, content: impl Into<Element<'a, Message>>) -> Self {
+        Self {
+            state,
+            key,
+            content: content.into(),
+            accept: Box::new(|_| true),
+            highlight: None,
+        }
+    }
+
+    /// Sets the predicate deciding if a payload can be dropped on the target.
+    pub fn accept(mut self, f: impl Fn(&P) -> bool + 'a) -> Self {
+        self.accept = Box::new(f);
+        self
+    }
+
+    /// Sets the color drawn over the target while an accepted payload hovers it.
+    pub fn highlight(mut self, color: Color) -> Self {
+        self.highlight = Some(color);
+        self
+    }
+}
+
+impl<'a, Message, P, K> Widget<Message, Renderer> for Target<'a, Message, P, K>
+where
+    P: Clone,
+    K: Clone + PartialEq,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        self.content.layout(renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        defaults: &Defaults,
+        layout: Layout<'_>,
+        cursor_position: Point,
+    ) -> (Primitive, mouse::Interaction) {
+        let (content, interaction) = self
+            .content
+            .draw(renderer, defaults, layout, cursor_position);
+        let is_hovered = self.state.session.borrow().hovered.as_ref() == Some(&self.key);
+
+        match self.highlight {
+            Some(color) if is_hovered => (
+                Primitive::Group {
+                    primitives: vec![
+                        content,
+                        Primitive::Quad {
+                            bounds: layout.bounds(),
+                            background: Background::Color(color),
+                            border_radius: 0,
+                            border_width: 0,
+                            border_color: Color::TRANSPARENT,
+                        },
+                    ],
+                },
+                interaction,
+            ),
+            _ => (content, interaction),
+        }
+    }
+
+    fn hash_layout(&self, state: &mut Hasher) {
+        struct Marker;
+        std::any::TypeId::of::<Marker>().hash(state);
+
+        self.content.hash_layout(state);
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        messages: &mut Vec<Message>,
+        renderer: &Renderer,
+        clipboard: Option<&dyn Clipboard>,
+    ) {
+        self.content.on_event(
+            event.clone(),
+            layout,
+            cursor_position,
+            messages,
+            renderer,
+            clipboard,
+        );
+
+        if let Event::Mouse(mouse::Event::CursorMoved { .. }) = event {
+            let accept = &self.accept;
+            self.state
+                .session
+                .borrow_mut()
+                .claim(&self.key, layout.bounds(), |payload| accept(payload));
+        }
+    }
+}
+
+impl<'a, Message, P, K> From<Target<'a, Message, P, K>> for Element<'a, Message>
+where
+    Message: 'a,
+    P: 'a + Clone,
+    K: 'a + Clone + PartialEq,
+{
+    fn from(target: Target<'a, Message, P, K>) -> Element<'a, Message> {
+        Element::new(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moved(x: f32, y: f32) -> Event {
+        Event::Mouse(mouse::Event::CursorMoved { x, y })
+    }
+
+    fn released() -> Event {
+        Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+    }
+
+    fn bounds(x: f32, y: f32) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width: 100.0,
+            height: 100.0,
+        }
+    }
+
+    /// Returns a session dragging "kick" from the source 1, with the cursor at (20, 10).
+    fn dragging() -> Session<&'static str, usize> {
+        let mut session = Session::new();
+        let _ = session.before(&moved(10.0, 10.0));
+        session.press(&1, &"kick");
+        let _ = session.before(&moved(20.0, 10.0));
+
+        session
+    }
+
+    /// Moves the cursor over the targets, innermost first.
+    fn hover(
+        session: &mut Session<&'static str, usize>,
+        position: Point,
+        targets: &[(usize, Rectangle, bool)],
+    ) -> Option<Transition<&'static str, usize>> {
+        let event = moved(position.x, position.y);
+        let before = session.before(&event);
+        assert_eq!(before, None);
+
+        for (key, bounds, accepts) in targets {
+            session.claim(key, *bounds, |_| *accepts);
+        }
+
+        session.after(&event)
+    }
+
+    #[test]
+    fn starts_dragging_past_the_threshold() {
+        let mut session = Session::<&str, usize>::new();
+        let _ = session.before(&moved(10.0, 10.0));
+        session.press(&1, &"kick");
+
+        assert_eq!(session.before(&moved(12.0, 10.0)), None);
+        assert_eq!(session.payload(), None);
+
+        assert_eq!(
+            session.before(&moved(20.0, 10.0)),
+            Some(Transition::Started("kick"))
+        );
+        assert_eq!(session.payload(), Some(&"kick"));
+        assert_eq!(session.translation(), Some(Vector::new(10.0, 0.0)));
+    }
+
+    #[test]
+    fn tells_the_dragged_source_by_its_key() {
+        let mut session = Session::<&str, usize>::new();
+        let _ = session.before(&moved(10.0, 10.0));
+        session.press(&1, &"kick");
+        assert!(!session.is_dragged(&1));
+
+        let _ = session.before(&moved(20.0, 10.0));
+        assert!(session.is_dragged(&1));
+        assert!(!session.is_dragged(&2));
+    }
+
+    #[test]
+    fn the_innermost_source_gets_the_press() {
+        let mut session = Session::<&str, usize>::new();
+        session.press(&1, &"kick");
+        session.press(&2, &"snare");
+
+        assert_eq!(
+            session.before(&moved(20.0, 0.0)),
+            Some(Transition::Started("kick"))
+        );
+        assert!(session.is_dragged(&1));
+    }
+
+    #[test]
+    fn hovers_targets_accepting_the_payload() {
+        let mut session = dragging();
+        let position = Point::new(30.0, 10.0);
+
+        assert_eq!(
+            hover(&mut session, position, &[(2, bounds(0.0, 0.0), false)]),
+            None
+        );
+        assert_eq!(
+            hover(&mut session, position, &[(3, bounds(0.0, 0.0), true)]),
+            Some(Transition::Hovered(Some(3)))
+        );
+        assert_eq!(
+            hover(&mut session, position, &[(3, bounds(0.0, 0.0), true)]),
+            None
+        );
+        assert_eq!(
+            hover(&mut session, position, &[(3, bounds(200.0, 0.0), true)]),
+            Some(Transition::Hovered(None))
+        );
+    }
+
+    #[test]
+    fn the_innermost_target_gets_the_claim() {
+        let mut session = dragging();
+        let targets = [(3, bounds(0.0, 0.0), true), (4, bounds(0.0, 0.0), true)];
+
+        assert_eq!(
+            hover(&mut session, Point::new(30.0, 10.0), &targets),
+            Some(Transition::Hovered(Some(3)))
+        );
+    }
+
+    #[test]
+    fn drops_the_payload_on_the_hovered_target() {
+        let mut session = dragging();
+        let _ = hover(
+            &mut session,
+            Point::new(30.0, 10.0),
+            &[(3, bounds(0.0, 0.0), true)],
+        );
+
+        assert_eq!(
+            session.after(&released()),
+            Some(Transition::Dropped("kick", 3))
+        );
+        assert_eq!(session.payload(), None);
+        assert_eq!(session.hovered, None);
+        assert_eq!(session.translation(), None);
+    }
+
+    #[test]
+    fn cancels_outside_of_targets() {
+        let mut session = dragging();
+
+        assert_eq!(
+            session.after(&released()),
+            Some(Transition::Cancelled("kick"))
+        );
+        assert!(!session.is_dragged(&1));
+    }
+
+    #[test]
+    fn releasing_before_the_threshold_is_a_click() {
+        let mut session = Session::<&str, usize>::new();
+        session.press(&1, &"kick");
+
+        assert_eq!(session.after(&released()), None);
+        assert_eq!(session.before(&moved(20.0, 0.0)), None);
+    }
+}
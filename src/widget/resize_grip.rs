@@ -0,0 +1,189 @@
+//! Let the user resize the view by dragging its corner.
+//!
+//! A [`ResizeGrip`] is meant to be placed in the bottom right corner of the view. While it's
+//! dragged, it produces the size the view should have for its corner to follow the cursor,
+//! which the application can pass to `resize::request`:
+//!
+//! ```
+//! use iced_nsview::resize_grip::{self, ResizeGrip};
+//! use iced_nsview::{resize, Command, Size};
+//!
+//! #[derive(Debug, Clone)]
+//! enum Message {
+//!     Resize(Size),
+//! }
+//!
+//! fn grip(state: &mut resize_grip::State) -> ResizeGrip<'_, Message> {
+//!     ResizeGrip::new(state, Message::Resize)
+//! }
+//!
+//! fn update(message: Message) -> Command<Message> {
+//!     match message {
+//!         Message::Resize(size) => resize::request(size),
+//!     }
+//! }
+//! ```
+//!
+//! [`ResizeGrip`]: struct.ResizeGrip.html
+
+use std::hash::Hash;
+
+use iced_native::{layout, mouse, Clipboard, Event, Hasher, Layout, Widget};
+use iced_wgpu::{Defaults, Primitive};
+
+use crate::{Background, Color, Element, Length, Point, Rectangle, Renderer, Size, Vector};
+
+/// The local state of a [`ResizeGrip`].
+///
+/// [`ResizeGrip`]: struct.ResizeGrip.html
+#[derive(Debug, Clone, Copy, Default)]
+pub struct State {
+    // The offset from the cursor to the bottom right corner of the grip, while it's dragged.
+    grab: Option<Vector>,
+}
+
+impl State {
+    /// Creates an idle state.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` while the grip is dragged.
+    pub fn is_dragging(&self) -> bool {
+        self.grab.is_some()
+    }
+}
+
+/// A grip resizing the view when dragged.
+pub struct ResizeGrip<'a, Message> {
+    state: &'a mut State,
+    on_resize: Box<dyn Fn(Size) -> Message + 'a>,
+    size: u16,
+    color: Color,
+}
+
+impl<'a, Message> ResizeGrip<'a, Message> {
+    /// Creates a grip which produces the requested size of the view with `on_resize`.
+    pub fn new(state: &'a mut State, on_resize: impl Fn(Size) -> Message + 'a) -> Self {
+        Self {
+            state,
+            on_resize: Box::new(on_resize),
+            size: 16,
+            color: Color::from_rgba(0.0, 0.0, 0.0, 0.4),
+        }
+    }
+
+    /// Sets the width and height of the grip.
+    pub fn size(mut self, size: u16) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Sets the color of the dots of the grip.
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl<'a, Message> Widget<Message, Renderer> for ResizeGrip<'a, Message> {
+    fn width(&self) -> Length {
+        Length::Units(self.size)
+    }
+
+    fn height(&self) -> Length {
+        Length::Units(self.size)
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width()).height(self.height());
+
+        layout::Node::new(limits.resolve(Size::ZERO))
+    }
+
+    fn draw(
+        &self,
+        _renderer: &mut Renderer,
+        _defaults: &Defaults,
+        layout: Layout<'_>,
+        cursor_position: Point,
+    ) -> (Primitive, mouse::Interaction) {
+        let bounds = layout.bounds();
+        // A triangle of dots pointing to the corner, three on each side.
+        let dot = (bounds.width.min(bounds.height) / 8.0).max(1.0);
+        let spacing = dot * 2.0;
+        let corner = Point::new(bounds.x + bounds.width, bounds.y + bounds.height);
+        let primitives = (0..3)
+            .flat_map(|row| (0..3 - row).map(move |column| (row, column)))
+            .map(|(row, column)| Primitive::Quad {
+                bounds: Rectangle {
+                    x: corner.x - spacing * (column as f32 + 1.0),
+                    y: corner.y - spacing * (row as f32 + 1.0),
+                    width: dot,
+                    height: dot,
+                },
+                background: Background::Color(self.color),
+                border_radius: 0,
+                border_width: 0,
+                border_color: Color::TRANSPARENT,
+            })
+            .collect();
+
+        let interaction = if self.state.is_dragging() {
+            mouse::Interaction::Grabbing
+        } else if bounds.contains(cursor_position) {
+            mouse::Interaction::Grab
+        } else {
+            mouse::Interaction::default()
+        };
+
+        (Primitive::Group { primitives }, interaction)
+    }
+
+    fn hash_layout(&self, state: &mut Hasher) {
+        struct Marker;
+        std::any::TypeId::of::<Marker>().hash(state);
+
+        self.size.hash(state);
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        messages: &mut Vec<Message>,
+        _renderer: &Renderer,
+        _clipboard: Option<&dyn Clipboard>,
+    ) {
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let bounds = layout.bounds();
+                if bounds.contains(cursor_position) {
+                    let corner = Point::new(bounds.x + bounds.width, bounds.y + bounds.height);
+                    self.state.grab = Some(corner - cursor_position);
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { x, y }) => {
+                if let Some(grab) = self.state.grab {
+                    // The origin of the view stays put, so its size is the position of the corner.
+                    let corner = Point::new(x, y) + grab;
+                    messages.push((self.on_resize)(Size::new(corner.x, corner.y)));
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                self.state.grab = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'a, Message> From<ResizeGrip<'a, Message>> for Element<'a, Message>
+where
+    Message: 'a,
+{
+    fn from(grip: ResizeGrip<'a, Message>) -> Element<'a, Message> {
+        Element::new(grip)
+    }
+}